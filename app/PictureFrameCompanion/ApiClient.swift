@@ -109,7 +109,9 @@ final class ApiClient: ObservableObject {
             let (respData, resp) = try await URLSession.shared.upload(
                 for: req,
                 from: body)
-            guard (resp as? HTTPURLResponse)?.statusCode == 201 else {
+            // 201 Created, or 200 OK when the picture is already on the frame
+            guard let code = (resp as? HTTPURLResponse)?.statusCode, (200..<300).contains(code)
+            else {
                 let text = String(data: respData, encoding: .utf8) ?? "<binary>"
                 logger.error("Upload failed: \(text, privacy: .public)")
                 throw URLError(.badServerResponse)
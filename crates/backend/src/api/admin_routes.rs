@@ -0,0 +1,92 @@
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+    routing,
+};
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+
+use crate::{
+    backup,
+    common::{ApiError, ApiKey, ApiResult, AppState},
+    fsck::{self, Report},
+};
+
+/// Size of the pipe between the archive writer and the response body.
+const STREAM_BUFFER: usize = 64 * 1024;
+
+pub fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/backup", routing::get(backup))
+        .route(
+            "/api/admin/fsck",
+            routing::get(check_library).post(repair_library),
+        )
+}
+
+/// Streams a tar archive of the database, the data dir and the frame
+/// settings; see [`backup`](crate::backup). A manifest with checksums comes
+/// last, so a broken download is caught on restore.
+async fn backup(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+) -> ApiResult<Response> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let snapshot = backup::snapshot(&state.repo, &state.settings).await?;
+    let (reader, writer) = tokio::io::duplex(STREAM_BUFFER);
+    let writer = SyncIoBridge::new(writer);
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = snapshot.write(writer) {
+            tracing::error!("backup failed: {e:#}");
+        }
+    });
+
+    let filename = format!(
+        "picture_frame-{}.tar",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-tar".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(reader)),
+    )
+        .into_response())
+}
+
+/// Reports inconsistencies between the database and the data dir.
+async fn check_library(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+) -> ApiResult<Json<Report>> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    Ok(Json(
+        fsck::check(&state.repo, &state.settings, false).await?,
+    ))
+}
+
+/// Like [`check_library`], but also repairs what it finds. Unlike
+/// `backend fsck --repair`, this is safe while the backend runs, as the
+/// repair waits for files that are being registered.
+async fn repair_library(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+) -> ApiResult<Json<Report>> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    Ok(Json(fsck::check(&state.repo, &state.settings, true).await?))
+}
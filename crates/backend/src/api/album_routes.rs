@@ -0,0 +1,203 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing,
+};
+use serde::Deserialize;
+
+use crate::{
+    common::{ApiError, ApiKey, ApiResult, AppState},
+    db::{Album, Picture, is_unique_violation},
+    library,
+};
+
+#[derive(Deserialize)]
+struct AlbumRequest {
+    name: String,
+}
+
+impl AlbumRequest {
+    fn name(&self) -> ApiResult<&str> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err(ApiError::BadRequest("album name must not be empty".into()));
+        }
+        Ok(name)
+    }
+}
+
+pub fn album_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/albums", routing::get(list_albums).post(create_album))
+        .route(
+            "/api/albums/{id}",
+            routing::get(get_album)
+                .patch(rename_album)
+                .delete(delete_album),
+        )
+        .route(
+            "/api/albums/{id}/pictures",
+            routing::get(list_album_pictures),
+        )
+        .route(
+            "/api/albums/{id}/pictures/{picture_id}",
+            routing::put(add_album_picture).delete(remove_album_picture),
+        )
+}
+
+fn name_taken(e: anyhow::Error, name: &str) -> ApiError {
+    if is_unique_violation(&e) {
+        ApiError::Conflict(format!("an album named {name:?} already exists"))
+    } else {
+        e.into()
+    }
+}
+
+async fn list_albums(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+) -> ApiResult<Json<Vec<Album>>> {
+    if scope != "ro" && scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    Ok(Json(state.repo.list_albums().await?))
+}
+
+async fn create_album(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Json(req): Json<AlbumRequest>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let name = req.name()?;
+    let album = state
+        .repo
+        .create_album(name)
+        .await
+        .map_err(|e| name_taken(e, name))?;
+    library::changed();
+
+    Ok((StatusCode::CREATED, Json(album)))
+}
+
+async fn get_album(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Album>> {
+    if scope != "ro" && scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let album = state.repo.get_album(&id).await?.ok_or(ApiError::NotFound)?;
+    Ok(Json(album))
+}
+
+async fn rename_album(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<AlbumRequest>,
+) -> ApiResult<Json<Album>> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let name = req.name()?;
+    let found = state
+        .repo
+        .rename_album(&id, name)
+        .await
+        .map_err(|e| name_taken(e, name))?;
+    if !found {
+        return Err(ApiError::NotFound);
+    }
+    library::changed();
+
+    let album = state.repo.get_album(&id).await?.ok_or(ApiError::NotFound)?;
+    Ok(Json(album))
+}
+
+/// Deletes the album only; its pictures stay in the library.
+async fn delete_album(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    if !state.repo.delete_album(&id).await? {
+        return Err(ApiError::NotFound);
+    }
+    library::changed();
+
+    let settings = state.settings.get().await;
+    if settings.active_albums.contains(&id) {
+        state
+            .settings
+            .update(|s| s.active_albums.retain(|a| *a != id))
+            .await
+            .map_err(|e| ApiError::Internal(e.into()))?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_album_pictures(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Vec<Picture>>> {
+    if scope != "ro" && scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    state.repo.get_album(&id).await?.ok_or(ApiError::NotFound)?;
+    Ok(Json(state.repo.list_album_pictures(&id).await?))
+}
+
+async fn add_album_picture(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path((id, picture_id)): Path<(String, String)>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    state.repo.get_album(&id).await?.ok_or(ApiError::NotFound)?;
+    state
+        .repo
+        .get_picture(&picture_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    state.repo.add_album_picture(&id, &picture_id).await?;
+    library::changed();
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn remove_album_picture(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path((id, picture_id)): Path<(String, String)>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    if !state.repo.remove_album_picture(&id, &picture_id).await? {
+        return Err(ApiError::NotFound);
+    }
+    library::changed();
+
+    Ok(StatusCode::NO_CONTENT)
+}
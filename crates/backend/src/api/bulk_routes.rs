@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+
+use axum::{Json, Router, extract::State, routing};
+use serde::{Deserialize, Serialize};
+
+use super::picture_routes::{MAX_CAPTION_LEN, text_field};
+use crate::{
+    common::{ApiError, ApiKey, ApiResult, AppState},
+    db::{BulkOutcome, normalize_tag},
+    library, trash,
+};
+
+const MAX_BULK_IDS: usize = 1000;
+
+#[derive(Deserialize)]
+struct BulkRequest {
+    ids: Vec<String>,
+    #[serde(flatten)]
+    action: BulkAction,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum BulkAction {
+    /// Move to the trash, like `DELETE /api/pictures/{id}`.
+    Delete,
+    AddToAlbum {
+        album_id: String,
+    },
+    Tag {
+        tag: String,
+    },
+    Untag {
+        tag: String,
+    },
+    /// A missing, null or blank caption clears it.
+    SetCaption {
+        #[serde(default)]
+        caption: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+struct BulkResult {
+    id: String,
+    status: BulkOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BulkResponse {
+    results: Vec<BulkResult>,
+}
+
+pub fn bulk_routes() -> Router<AppState> {
+    Router::new().route("/api/pictures/bulk", routing::post(bulk))
+}
+
+/// Applies one action to many pictures. The database side of each action
+/// runs in a single transaction; the result lists what happened per id.
+async fn bulk(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Json(req): Json<BulkRequest>,
+) -> ApiResult<Json<BulkResponse>> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let mut seen = HashSet::new();
+    let ids: Vec<String> = req
+        .ids
+        .into_iter()
+        .filter(|id| seen.insert(id.clone()))
+        .collect();
+    if ids.is_empty() {
+        return Err(ApiError::BadRequest("ids must not be empty".into()));
+    }
+    if ids.len() > MAX_BULK_IDS {
+        return Err(ApiError::BadRequest(format!(
+            "at most {MAX_BULK_IDS} ids per request"
+        )));
+    }
+
+    let results = match req.action {
+        BulkAction::Delete => delete(&state, ids).await?,
+        BulkAction::AddToAlbum { album_id } => {
+            if state.repo.get_album(&album_id).await?.is_none() {
+                return Err(ApiError::NotFound);
+            }
+            let outcomes = state
+                .repo
+                .add_pictures_to_album(&album_id, ids.clone())
+                .await?;
+            zip(ids, outcomes)
+        }
+        BulkAction::Tag { tag } => {
+            let tag = valid_tag(&tag)?;
+            let outcomes = state.repo.tag_pictures(ids.clone(), &tag).await?;
+            zip(ids, outcomes)
+        }
+        BulkAction::Untag { tag } => {
+            let tag = valid_tag(&tag)?;
+            let outcomes = state.repo.untag_pictures(ids.clone(), &tag).await?;
+            zip(ids, outcomes)
+        }
+        BulkAction::SetCaption { caption } => {
+            let caption = text_field("caption", caption, MAX_CAPTION_LEN)?;
+            let outcomes = state
+                .repo
+                .set_pictures_caption(ids.clone(), caption)
+                .await?;
+            zip(ids, outcomes)
+        }
+    };
+    library::changed();
+
+    Ok(Json(BulkResponse { results }))
+}
+
+async fn delete(state: &AppState, ids: Vec<String>) -> ApiResult<Vec<BulkResult>> {
+    let outcomes = trash::trash_pictures(&state.repo, &state.settings, ids.clone()).await?;
+    let mut results = Vec::with_capacity(ids.len());
+    for (id, outcome) in ids.into_iter().zip(outcomes) {
+        match outcome {
+            Ok(Some(_)) => results.push(result(id, BulkOutcome::Done)),
+            Ok(None) => results.push(result(id, BulkOutcome::NotFound)),
+            Err(e) => {
+                tracing::warn!("failed to move {id} to the trash: {e:#}");
+                results.push(BulkResult {
+                    id,
+                    status: BulkOutcome::Failed,
+                    error: Some(e.to_string()),
+                });
+            }
+        }
+    }
+    Ok(results)
+}
+
+fn valid_tag(raw: &str) -> ApiResult<String> {
+    normalize_tag(raw).ok_or_else(|| ApiError::BadRequest(format!("invalid tag {raw:?}")))
+}
+
+fn result(id: String, status: BulkOutcome) -> BulkResult {
+    BulkResult {
+        id,
+        status,
+        error: None,
+    }
+}
+
+fn zip(ids: Vec<String>, outcomes: Vec<BulkOutcome>) -> Vec<BulkResult> {
+    ids.into_iter()
+        .zip(outcomes)
+        .map(|(id, status)| result(id, status))
+        .collect()
+}
@@ -1,5 +1,15 @@
+mod admin_routes;
+mod album_routes;
+mod bulk_routes;
 mod picture_routes;
 mod settings_routes;
+mod trash_routes;
+mod upload_routes;
 
+pub use admin_routes::admin_routes;
+pub use album_routes::album_routes;
+pub use bulk_routes::bulk_routes;
 pub use picture_routes::picture_routes;
 pub use settings_routes::settings_routes;
+pub use trash_routes::trash_routes;
+pub use upload_routes::upload_routes;
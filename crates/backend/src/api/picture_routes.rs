@@ -1,166 +1,746 @@
 use axum::{
     Json, Router,
-    extract::{Multipart, Path, State},
+    extract::{
+        DefaultBodyLimit, Multipart, Path, Query, Request, State,
+        multipart::{Field, MultipartError},
+    },
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     routing,
 };
 use futures::StreamExt;
-use mime::{IMAGE_JPEG, IMAGE_PNG, Mime};
+use mime::Mime;
+use serde::{Deserialize, Deserializer, Serialize};
 use tokio::io::AsyncWriteExt;
 
+use libs::{
+    frame_settings::TagFilter,
+    framing::{Crop, FocalPoint, Rotation},
+    schedule::ScheduleRule,
+};
+
 use crate::{
     CONFIG,
-    common::{ApiKey, AppState},
-    db::Picture,
+    common::{self, ApiError, ApiKey, ApiResult, AppState},
+    db::{
+        PageStart, Picture, PictureEdit, PictureFilter, PictureSort, SortKey, SortValue, Tag,
+        normalize_tag,
+    },
+    ingest::{self, Ingested},
+    library,
+    media::derivatives::{self, DerivativeSize},
+    trash,
 };
 
+#[derive(Deserialize)]
+struct UploadQuery {
+    /// Store the upload even if the same content is already in the library.
+    #[serde(default)]
+    allow_duplicate: bool,
+}
+
+#[derive(Deserialize)]
+struct ImportRequest {
+    url: String,
+    /// Store the picture even if the same content is already in the library.
+    #[serde(default)]
+    allow_duplicate: bool,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum UploadOutcome {
+    Created { picture: Picture },
+    Duplicate { picture: Picture },
+    Rejected { reason: String },
+}
+
+/// Per-file entry of the upload response.
+#[derive(Serialize)]
+struct UploadResult {
+    /// Client-side file name of the multipart part, if it had one.
+    name: Option<String>,
+    #[serde(flatten)]
+    outcome: UploadOutcome,
+}
+
+impl UploadResult {
+    fn rejected(name: Option<String>, err: ApiError) -> Self {
+        let reason = match err {
+            ApiError::Internal(e) => {
+                tracing::error!(error = ?e, "upload of {name:?} failed");
+                "internal error".to_owned()
+            }
+            e => e.to_string(),
+        };
+        UploadResult {
+            name,
+            outcome: UploadOutcome::Rejected { reason },
+        }
+    }
+}
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+/// Tag filters are comma-separated lists; dates are unix millis, RFC 3339
+/// timestamps or `YYYY-MM-DD` (UTC).
+///
+/// Without `limit` and `cursor` every match is returned as a bare array, as
+/// before; with either, one page is returned in a [`PicturePage`].
+#[derive(Deserialize)]
+struct ListQuery {
+    /// Pictures carrying all of these tags.
+    tag: Option<String>,
+    /// Pictures carrying at least one of these tags.
+    any_tag: Option<String>,
+    /// Pictures carrying none of these tags.
+    exclude_tag: Option<String>,
+    added_after: Option<String>,
+    added_before: Option<String>,
+    taken_after: Option<String>,
+    taken_before: Option<String>,
+    favorite: Option<bool>,
+    #[serde(default)]
+    sort: SortKey,
+    /// Defaults to descending for dates and ascending for file names.
+    order: Option<SortOrder>,
+    limit: Option<u32>,
+    cursor: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Serialize)]
+struct PicturePage {
+    items: Vec<Picture>,
+    /// Matches across all pages.
+    total: u64,
+    /// Pass as `cursor` to get the next page; `None` on the last page.
+    next_cursor: Option<String>,
+}
+
+/// Position after the last picture of a page, tied to the sort it was made for.
+#[derive(Serialize, Deserialize)]
+struct ListCursor {
+    sort: SortKey,
+    desc: bool,
+    value: SortValue,
+    id: String,
+}
+
+const MAX_TITLE_LEN: usize = 200;
+pub(super) const MAX_CAPTION_LEN: usize = 2000;
+/// Upper bound for picture weights and the favorite multiplier.
+pub(super) const MAX_WEIGHT: u32 = 10;
+const MAX_SCHEDULE_RULES: usize = 16;
+
+/// Fields left out stay as they are; `null` or an empty string clears them.
+#[derive(Deserialize)]
+struct PictureUpdate {
+    #[serde(default, deserialize_with = "nullable")]
+    title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    caption: Option<Option<String>>,
+    favorite: Option<bool>,
+    weight: Option<u32>,
+    /// Replaces all rules; an empty list shows the picture always.
+    schedule: Option<Vec<ScheduleRule>>,
+    #[serde(default, deserialize_with = "nullable")]
+    crop: Option<Option<Crop>>,
+    #[serde(default, deserialize_with = "nullable")]
+    focal_point: Option<Option<FocalPoint>>,
+}
+
+/// Tells an explicit `null` (`Some(None)`) apart from a missing field (`None`).
+fn nullable<'de, D, T>(de: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(de).map(Some)
+}
+
+#[derive(Deserialize)]
+struct ThumbnailQuery {
+    #[serde(default)]
+    size: DerivativeSize,
+}
+
 pub fn picture_routes() -> Router<AppState> {
     Router::new()
         .route(
             "/api/pictures",
-            routing::get(list_pictures).post(upload_picture),
+            routing::get(list_pictures)
+                .post(upload_picture)
+                .layer(DefaultBodyLimit::max(CONFIG.backend_max_upload_bytes)),
         )
+        .route("/api/pictures/import", routing::post(import_picture))
         .route(
             "/api/pictures/{id}/pin",
             routing::put(pin_picture).delete(unpin_picture),
         )
-        .route("/api/pictures/{id}", routing::delete(delete_picture))
+        .route(
+            "/api/pictures/{id}",
+            routing::patch(update_picture).delete(delete_picture),
+        )
+        .route("/api/pictures/{id}/rotate", routing::post(rotate_picture))
+        .route(
+            "/api/pictures/{id}/tags/{tag}",
+            routing::put(add_tag).delete(remove_tag),
+        )
+        .route("/api/tags", routing::get(list_tags))
+        .route("/api/pictures/{id}/file", routing::get(download_picture))
+        .route("/api/pictures/{id}/thumbnail", routing::get(get_thumbnail))
 }
 
 async fn list_pictures(
     ApiKey { scope, .. }: ApiKey,
     State(state): State<AppState>,
-) -> Result<Json<Vec<Picture>>, StatusCode> {
+    Query(query): Query<ListQuery>,
+) -> ApiResult<Response> {
+    if scope != "ro" && scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let split = |list: &Option<String>| tag_list(list.iter().flat_map(|l| l.split(',')));
+    let time =
+        |name: &str, raw: &Option<String>| raw.as_deref().map(|r| parse_time(name, r)).transpose();
+    let filter = PictureFilter {
+        tags: TagFilter {
+            all: split(&query.tag)?,
+            any: split(&query.any_tag)?,
+            exclude: split(&query.exclude_tag)?,
+        },
+        added_after: time("added_after", &query.added_after)?,
+        added_before: time("added_before", &query.added_before)?,
+        taken_after: time("taken_after", &query.taken_after)?,
+        taken_before: time("taken_before", &query.taken_before)?,
+        favorite: query.favorite,
+    };
+    let sort = PictureSort {
+        key: query.sort,
+        descending: match query.order {
+            Some(order) => matches!(order, SortOrder::Desc),
+            None => query.sort != SortKey::Filename,
+        },
+    };
+
+    if query.limit.is_none() && query.cursor.is_none() {
+        let pics = state.repo.list_pictures(&filter, sort, None, None).await?;
+        return Ok(Json(pics).into_response());
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(ApiError::BadRequest(format!(
+            "limit must be between 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+    let start = match &query.cursor {
+        Some(cursor) => {
+            let cursor: ListCursor = common::decode_cursor(cursor)?;
+            if cursor.sort != sort.key || cursor.desc != sort.descending {
+                return Err(ApiError::BadRequest(
+                    "cursor belongs to a different sort order".into(),
+                ));
+            }
+            Some(PageStart {
+                value: cursor.value,
+                id: cursor.id,
+            })
+        }
+        None => None,
+    };
+
+    // one extra row tells whether there is a next page
+    let mut items = state
+        .repo
+        .list_pictures(&filter, sort, start, Some(limit + 1))
+        .await?;
+    let next_cursor = if items.len() > limit as usize {
+        items.truncate(limit as usize);
+        items.last().map(|last| {
+            common::encode_cursor(&ListCursor {
+                sort: sort.key,
+                desc: sort.descending,
+                value: sort.key.value_of(last),
+                id: last.id.clone(),
+            })
+        })
+    } else {
+        None
+    };
+    let total = state.repo.count_matching_pictures(&filter).await?;
+
+    Ok(Json(PicturePage {
+        items,
+        total,
+        next_cursor,
+    })
+    .into_response())
+}
+
+/// Unix millis, an RFC 3339 timestamp or a `YYYY-MM-DD` date (UTC midnight).
+fn parse_time(name: &str, raw: &str) -> ApiResult<i64> {
+    if let Ok(millis) = raw.parse::<i64>() {
+        return Ok(millis);
+    }
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(raw) {
+        return Ok(t.timestamp_millis());
+    }
+    if let Ok(d) = chrono::NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        return Ok(d
+            .and_time(chrono::NaiveTime::MIN)
+            .and_utc()
+            .timestamp_millis());
+    }
+    Err(ApiError::BadRequest(format!("invalid {name} {raw:?}")))
+}
+
+/// Normalize user-supplied tags, rejecting the request if one is invalid.
+pub(super) fn tag_list<'a>(raw: impl IntoIterator<Item = &'a str>) -> ApiResult<Vec<String>> {
+    let mut tags = raw
+        .into_iter()
+        .map(|t| normalize_tag(t).ok_or_else(|| ApiError::BadRequest(format!("invalid tag {t:?}"))))
+        .collect::<ApiResult<Vec<_>>>()?;
+    tags.sort();
+    tags.dedup();
+    Ok(tags)
+}
+
+async fn list_tags(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+) -> ApiResult<Json<Vec<Tag>>> {
+    if scope != "ro" && scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    Ok(Json(state.repo.list_tags().await?))
+}
+
+async fn add_tag(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path((id, tag)): Path<(String, String)>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let tag =
+        normalize_tag(&tag).ok_or_else(|| ApiError::BadRequest(format!("invalid tag {tag:?}")))?;
+    state
+        .repo
+        .get_picture(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    state.repo.add_picture_tag(&id, &tag).await?;
+    library::changed();
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn remove_tag(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path((id, tag)): Path<(String, String)>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let tag = normalize_tag(&tag).ok_or(ApiError::NotFound)?;
+    if !state.repo.remove_picture_tag(&id, &tag).await? {
+        return Err(ApiError::NotFound);
+    }
+    library::changed();
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn download_picture(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    req: Request,
+) -> Result<Response, StatusCode> {
     if scope != "ro" && scope != "rw" {
         return Err(StatusCode::FORBIDDEN);
     }
 
-    let pics = state
+    let picture = state
         .repo
-        .list_pictures()
+        .get_picture(&id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Json(pics))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let path = std::path::Path::new(&CONFIG.backend_data_dir).join(&picture.filename);
+    Ok(common::serve_file(&path, req).await)
+}
+
+async fn get_thumbnail(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ThumbnailQuery>,
+    req: Request,
+) -> Result<Response, StatusCode> {
+    if scope != "ro" && scope != "rw" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let picture = state
+        .repo
+        .get_picture(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // normally generated right after upload; fall back to generating on demand
+    let filename = picture.filename;
+    let rotation = picture.rotation;
+    let path =
+        tokio::task::spawn_blocking(move || derivatives::ensure(&filename, query.size, rotation))
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map_err(|e| {
+                tracing::error!("cannot create derivative for {id}: {e:#}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+    Ok(common::serve_file(&path, req).await)
 }
 
+/// Accepts any number of `file` parts and streams each one to disk in turn.
+/// Other parts are ignored. A broken multipart stream aborts the request, but
+/// files registered before that point are kept.
 async fn upload_picture(
     ApiKey { scope, .. }: ApiKey,
     State(state): State<AppState>,
+    Query(query): Query<UploadQuery>,
     mut multipart: Multipart,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> ApiResult<impl IntoResponse> {
     if scope != "rw" {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::Forbidden);
     }
 
-    let mut field = multipart
-        .next_field()
-        .await
-        .map_err(|e| {
-            tracing::warn!("bad multipart: {e}");
-            StatusCode::BAD_REQUEST
-        })?
-        .ok_or(StatusCode::BAD_REQUEST)?;
+    let mut results = Vec::new();
+    let mut out_of_space = false;
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() != Some("file") {
+            tracing::debug!("ignoring multipart field {:?}", field.name());
+            continue;
+        }
+        let name = field.file_name().map(str::to_owned);
 
-    if field.name() != Some("file") {
-        return Err(StatusCode::BAD_REQUEST);
+        let declared = match field.content_type().map(str::parse::<Mime>).transpose() {
+            Ok(declared) => declared,
+            Err(_) => {
+                let ct = field.content_type().unwrap_or_default();
+                results.push(UploadResult::rejected(
+                    name,
+                    ApiError::UnsupportedMediaType(format!("invalid content type {ct}")),
+                ));
+                continue;
+            }
+        };
+
+        let staged = stage_field(&mut field).await?;
+        let outcome = ingest::register(
+            &state.repo,
+            &state.settings,
+            &staged,
+            declared,
+            None,
+            query.allow_duplicate,
+        )
+        .await;
+        results.push(match outcome {
+            Ok(Ingested::Created(picture)) => UploadResult {
+                name,
+                outcome: UploadOutcome::Created { picture },
+            },
+            Ok(Ingested::Duplicate(picture)) => UploadResult {
+                name,
+                outcome: UploadOutcome::Duplicate { picture },
+            },
+            Err(e) => {
+                out_of_space |= matches!(e, ApiError::InsufficientStorage(_));
+                UploadResult::rejected(name, e)
+            }
+        });
     }
 
-    let ct: Mime = field
-        .content_type()
-        .ok_or(StatusCode::UNSUPPORTED_MEDIA_TYPE)?
-        .parse()
-        .map_err(|_| StatusCode::UNSUPPORTED_MEDIA_TYPE)?;
+    if results.is_empty() {
+        return Err(ApiError::BadRequest(
+            "expected at least one multipart field named `file`".into(),
+        ));
+    }
 
-    let ext = if ct == IMAGE_JPEG {
-        "jpg"
-    } else if ct == IMAGE_PNG {
-        "png"
+    let created = results
+        .iter()
+        .any(|r| matches!(r.outcome, UploadOutcome::Created { .. }));
+    let all_rejected = results
+        .iter()
+        .all(|r| matches!(r.outcome, UploadOutcome::Rejected { .. }));
+    let status = if created {
+        StatusCode::CREATED
+    } else if all_rejected && out_of_space {
+        StatusCode::INSUFFICIENT_STORAGE
+    } else if all_rejected {
+        StatusCode::UNPROCESSABLE_ENTITY
     } else {
-        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        StatusCode::OK
     };
+    Ok((status, Json(results)))
+}
 
-    let file_id = uuid::Uuid::new_v4().to_string();
-    let filename = format!("{file_id}.{ext}");
+/// Downloads a picture and stores it like an upload, remembering the URL.
+async fn import_picture(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Json(req): Json<ImportRequest>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
 
-    let data_dir = std::path::Path::new(&CONFIG.backend_data_dir);
-    if !data_dir.exists() {
-        tokio::fs::create_dir_all(data_dir).await.map_err(|e| {
-            tracing::error!("cannot create data_dir {data_dir:?}: {e}");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let (staged, mut dest) = ingest::create_staging_file().await?;
+    if let Err(e) = state.fetcher.fetch(&req.url, &mut dest).await {
+        tracing::warn!("cannot import {}: {e}", req.url);
+        ingest::discard(&staged).await;
+        return Err(e.into());
     }
-    let path = data_dir.join(&filename);
-    tracing::debug!("saving {}", path.display());
+    drop(dest);
 
-    let mut dest = tokio::fs::File::create(&path).await.map_err(|e| {
-        tracing::error!("cannot open {path:?}: {e}");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    // servers often label pictures vaguely, so the content alone decides
+    let outcome = ingest::register(
+        &state.repo,
+        &state.settings,
+        &staged,
+        None,
+        Some(&req.url),
+        req.allow_duplicate,
+    )
+    .await?;
+    Ok(match outcome {
+        Ingested::Created(picture) => (
+            StatusCode::CREATED,
+            Json(UploadOutcome::Created { picture }),
+        ),
+        Ingested::Duplicate(picture) => {
+            (StatusCode::OK, Json(UploadOutcome::Duplicate { picture }))
+        }
+    })
+}
+
+/// Stream one multipart part into the staging area.
+async fn stage_field(field: &mut Field<'_>) -> ApiResult<std::path::PathBuf> {
+    let (staged, mut dest) = ingest::create_staging_file().await?;
+    tracing::debug!("staging {}", staged.display());
 
     while let Some(chunk) = field.next().await {
-        let bytes = chunk.map_err(|e| {
-            tracing::warn!("multipart read error: {e}");
-            StatusCode::BAD_REQUEST
-        })?;
-        if let Err(e) = dest.write_all(&bytes).await {
-            tracing::error!("write error on {path:?}: {e}");
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        let written = match chunk {
+            Ok(bytes) => dest.write_all(&bytes).await.map_err(|e| {
+                tracing::error!("write error on {staged:?}: {e}");
+                ApiError::write_failed(e)
+            }),
+            Err(e) => {
+                tracing::warn!("multipart read error: {e}");
+                Err(multipart_error(e))
+            }
+        };
+        if let Err(e) = written {
+            drop(dest);
+            ingest::discard(&staged).await;
+            return Err(e);
         }
     }
     dest.flush().await.ok(); // ignore flush error; already logged
     dest.sync_all().await.ok();
 
-    let saved = state.repo.add_picture(&filename).await.map_err(|e| {
-        tracing::error!("db error: {e}");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    Ok(staged)
+}
 
-    Ok((StatusCode::CREATED, Json(saved)))
+fn multipart_error(e: MultipartError) -> ApiError {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        ApiError::PayloadTooLarge(e.body_text())
+    } else {
+        ApiError::BadRequest(e.body_text())
+    }
 }
 
-async fn delete_picture(
+async fn update_picture(
     ApiKey { scope, .. }: ApiKey,
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<impl IntoResponse, StatusCode> {
+    Json(update): Json<PictureUpdate>,
+) -> ApiResult<Json<Picture>> {
     if scope != "rw" {
-        return Err(StatusCode::FORBIDDEN);
+        return Err(ApiError::Forbidden);
+    }
+
+    let title = update
+        .title
+        .map(|title| text_field("title", title, MAX_TITLE_LEN))
+        .transpose()?;
+    let caption = update
+        .caption
+        .map(|caption| text_field("caption", caption, MAX_CAPTION_LEN))
+        .transpose()?;
+    if let Some(weight) = update.weight {
+        check_weight("weight", weight)?;
+    }
+    if let Some(rules) = &update.schedule {
+        check_schedule(rules)?;
+    }
+    if let Some(Some(crop)) = update.crop
+        && !crop.is_valid()
+    {
+        return Err(ApiError::BadRequest(
+            "crop must be a non-empty rectangle within the picture".into(),
+        ));
+    }
+    if let Some(Some(focal_point)) = update.focal_point
+        && !focal_point.is_valid()
+    {
+        return Err(ApiError::BadRequest(
+            "focal_point must lie within the picture".into(),
+        ));
     }
 
-    let fname_opt = state
+    let edit = PictureEdit {
+        title,
+        caption,
+        favorite: update.favorite,
+        weight: update.weight,
+        schedule: update.schedule,
+        crop: update.crop,
+        focal_point: update.focal_point,
+    };
+    if !state.repo.update_picture(&id, edit).await? {
+        return Err(ApiError::NotFound);
+    }
+    library::changed();
+
+    let picture = state
         .repo
-        .delete_picture_and_return_filename(&id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .get_picture(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(Json(picture))
+}
 
-    let Some(fname) = fname_opt else {
-        return Err(StatusCode::NOT_FOUND);
+/// Sets the rotation override, replacing any previous one; `{"degrees": 0}`
+/// removes it.
+async fn rotate_picture(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(rotation): Json<Rotation>,
+) -> ApiResult<Json<Picture>> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+    if !rotation.is_valid() {
+        return Err(ApiError::BadRequest(
+            "degrees must be 0, 90, 180 or 270".into(),
+        ));
+    }
+
+    let picture = state
+        .repo
+        .get_picture(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if picture.rotation == rotation {
+        return Ok(Json(picture));
+    }
+    // crop and focal point are measured on the turned picture
+    let crop = picture.crop.map(|c| c.rotated(picture.rotation, rotation));
+    let focal_point = picture
+        .focal_point
+        .map(|f| f.rotated(picture.rotation, rotation));
+    if !state
+        .repo
+        .set_picture_rotation(&id, rotation, crop, focal_point)
+        .await?
+    {
+        return Err(ApiError::NotFound);
+    }
+    derivatives::regenerate(picture.filename, rotation).await;
+    library::changed();
+
+    let picture = state
+        .repo
+        .get_picture(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(Json(picture))
+}
+
+/// Trimmed text, `None` if blank.
+pub(super) fn text_field(
+    name: &str,
+    value: Option<String>,
+    max_len: usize,
+) -> ApiResult<Option<String>> {
+    let Some(value) = value.map(|v| v.trim().to_owned()).filter(|v| !v.is_empty()) else {
+        return Ok(None);
     };
+    if value.chars().count() > max_len {
+        return Err(ApiError::BadRequest(format!(
+            "{name} is limited to {max_len} characters"
+        )));
+    }
+    Ok(Some(value))
+}
 
-    let settings = state.settings.get().await;
-    if settings.pinned_image.as_ref() == Some(&fname) {
-        state
-            .settings
-            .update(|s| {
-                s.pinned_image = None;
-            })
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+pub(super) fn check_weight(name: &str, weight: u32) -> ApiResult<()> {
+    if !(1..=MAX_WEIGHT).contains(&weight) {
+        return Err(ApiError::BadRequest(format!(
+            "{name} must be between 1 and {MAX_WEIGHT}"
+        )));
     }
+    Ok(())
+}
 
-    let path = std::path::Path::new(&CONFIG.backend_data_dir).join(&fname);
-    match tokio::fs::remove_file(&path).await {
-        Ok(_) => tracing::debug!("removed file {}", path.display()),
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            tracing::warn!("file already gone: {}", path.display())
-        }
-        Err(e) => {
-            tracing::error!("failed to delete {path:?}: {e}");
-            // DB row is already gone; still return 204
-        }
+fn check_schedule(rules: &[ScheduleRule]) -> ApiResult<()> {
+    if rules.len() > MAX_SCHEDULE_RULES {
+        return Err(ApiError::BadRequest(format!(
+            "at most {MAX_SCHEDULE_RULES} schedule rules"
+        )));
     }
+    if let Some(problem) = rules.iter().find_map(ScheduleRule::problem) {
+        return Err(ApiError::BadRequest(problem.into()));
+    }
+    Ok(())
+}
 
+/// Moves the picture to the trash; see `trash_routes` for restoring it.
+async fn delete_picture(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    if trash::trash_picture(&state.repo, &state.settings, &id)
+        .await?
+        .is_none()
+    {
+        return Err(ApiError::NotFound);
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -224,3 +804,80 @@ async fn unpin_picture(
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use libs::frame_settings::SharedSettings;
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::{
+        db::{PictureMetadata, Repository},
+        fetch::Fetcher,
+    };
+
+    /// State over a fresh in-memory database.
+    fn state() -> AppState {
+        // one connection, as each in-memory connection is its own database
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .build(SqliteConnectionManager::memory())
+            .unwrap();
+        let repo = Repository::new(pool);
+        repo.init_schema().unwrap();
+        let settings = std::env::temp_dir().join(format!(
+            "picframe-test-{}-frame_settings.toml",
+            std::process::id()
+        ));
+        let state = AppState {
+            repo: Arc::new(repo),
+            settings: SharedSettings::load(settings.to_str().unwrap()).unwrap(),
+            fetcher: Arc::new(Fetcher::new(0, Duration::ZERO, false)),
+        };
+        // loading wrote the defaults; the test works on the copy in memory
+        std::fs::remove_file(settings).unwrap();
+        state
+    }
+
+    #[tokio::test]
+    async fn patch_on_trashed_picture_is_not_found() {
+        let state = state();
+        let picture = state
+            .repo
+            .add_picture("a.jpg", "hash", PictureMetadata::default(), None, false)
+            .await
+            .unwrap();
+        state.repo.trash_picture(&picture.id).await.unwrap();
+
+        let key = ApiKey {
+            id: "key".into(),
+            scope: "rw".into(),
+        };
+        let update = serde_json::from_value(serde_json::json!({
+            "title": "new title",
+            "favorite": true,
+            "weight": 5,
+        }))
+        .unwrap();
+        let result = update_picture(
+            key,
+            State(state.clone()),
+            Path(picture.id.clone()),
+            Json(update),
+        )
+        .await;
+        assert!(matches!(result, Err(ApiError::NotFound)));
+
+        let trashed = state
+            .repo
+            .get_trashed_picture(&picture.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(trashed.title, None);
+        assert!(!trashed.favorite);
+        assert_eq!(trashed.weight, picture.weight);
+    }
+}
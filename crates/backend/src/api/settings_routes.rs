@@ -1,17 +1,30 @@
-use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use axum::{Json, Router, extract::State, routing::get};
 use serde::Deserialize;
 
-use libs::frame_settings::FrameSettings;
+use libs::frame_settings::{CaptionPosition, FitMode, FrameSettings, ShuffleMode, TagFilter};
 
-use crate::common::AppState;
+use super::picture_routes::{check_weight, tag_list};
+use crate::common::{ApiError, ApiResult, AppState};
 
 #[derive(Deserialize)]
 pub struct PartialSettings {
     pub display_enabled: Option<bool>,
     pub rotate_interval_secs: Option<u64>,
     pub shuffle: Option<bool>,
+    pub shuffle_mode: Option<ShuffleMode>,
+    pub favorite_weight: Option<u32>,
+    pub active_albums: Option<Vec<String>>,
+    /// An empty filter clears it.
+    pub tag_filter: Option<TagFilter>,
+    pub caption_enabled: Option<bool>,
+    pub caption_font_size: Option<u16>,
+    pub caption_position: Option<CaptionPosition>,
+    pub fit_mode: Option<FitMode>,
 }
 
+/// Font sizes the display can sensibly render, in points.
+const CAPTION_FONT_SIZES: std::ops::RangeInclusive<u16> = 8..=128;
+
 pub fn settings_routes() -> Router<AppState> {
     Router::new().route("/api/settings", get(get_settings).patch(patch_settings))
 }
@@ -23,7 +36,38 @@ async fn get_settings(State(state): State<AppState>) -> Json<FrameSettings> {
 async fn patch_settings(
     State(state): State<AppState>,
     Json(chg): Json<PartialSettings>,
-) -> Result<Json<FrameSettings>, StatusCode> {
+) -> ApiResult<Json<FrameSettings>> {
+    if let Some(albums) = &chg.active_albums {
+        for id in albums {
+            if state.repo.get_album(id).await?.is_none() {
+                return Err(ApiError::BadRequest(format!("unknown album {id}")));
+            }
+        }
+    }
+
+    if let Some(size) = chg.caption_font_size
+        && !CAPTION_FONT_SIZES.contains(&size)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "caption_font_size must be between {} and {}",
+            CAPTION_FONT_SIZES.start(),
+            CAPTION_FONT_SIZES.end()
+        )));
+    }
+
+    if let Some(weight) = chg.favorite_weight {
+        check_weight("favorite_weight", weight)?;
+    }
+
+    let tag_filter = match chg.tag_filter {
+        Some(f) => Some(TagFilter {
+            all: tag_list(f.all.iter().map(String::as_str))?,
+            any: tag_list(f.any.iter().map(String::as_str))?,
+            exclude: tag_list(f.exclude.iter().map(String::as_str))?,
+        }),
+        None => None,
+    };
+
     state
         .settings
         .update(|s| {
@@ -36,8 +80,34 @@ async fn patch_settings(
             if let Some(v) = chg.shuffle {
                 s.shuffle = v;
             }
+            if let Some(v) = chg.shuffle_mode {
+                s.shuffle_mode = v;
+            }
+            if let Some(v) = chg.favorite_weight {
+                s.favorite_weight = v;
+            }
+            if let Some(mut v) = chg.active_albums {
+                v.sort();
+                v.dedup();
+                s.active_albums = v;
+            }
+            if let Some(v) = tag_filter {
+                s.tag_filter = (!v.is_empty()).then_some(v);
+            }
+            if let Some(v) = chg.caption_enabled {
+                s.caption_enabled = v;
+            }
+            if let Some(v) = chg.caption_font_size {
+                s.caption_font_size = v;
+            }
+            if let Some(v) = chg.caption_position {
+                s.caption_position = v;
+            }
+            if let Some(v) = chg.fit_mode {
+                s.fit_mode = v;
+            }
         })
         .await
         .map(Json)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        .map_err(|e| ApiError::Internal(e.into()))
 }
@@ -0,0 +1,95 @@
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing,
+};
+use serde::Serialize;
+
+use crate::{
+    common::{ApiError, ApiKey, ApiResult, AppState},
+    db::Picture,
+    trash,
+};
+
+#[derive(Serialize)]
+struct Purged {
+    purged: usize,
+}
+
+pub fn trash_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/trash", routing::get(list_trash).delete(empty_trash))
+        .route("/api/trash/{id}", routing::delete(purge_picture))
+        .route("/api/trash/{id}/restore", routing::post(restore_picture))
+}
+
+async fn list_trash(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+) -> ApiResult<Json<Vec<Picture>>> {
+    if scope != "ro" && scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    Ok(Json(state.repo.list_trashed_pictures().await?))
+}
+
+async fn restore_picture(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Picture>> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    if !trash::restore_picture(&state.repo, &id).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    let picture = state
+        .repo
+        .get_picture(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(Json(picture))
+}
+
+/// Deletes one trashed picture for good.
+async fn purge_picture(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    if trash::purge(&state.repo, &[id]).await? == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn empty_trash(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+) -> ApiResult<Json<Purged>> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let ids: Vec<String> = state
+        .repo
+        .list_trashed_pictures()
+        .await?
+        .into_iter()
+        .map(|p| p.id)
+        .collect();
+    let purged = trash::purge(&state.repo, &ids).await?;
+    tracing::info!("emptied trash: {purged} pictures");
+
+    Ok(Json(Purged { purged }))
+}
@@ -0,0 +1,281 @@
+//! Resumable uploads for large files on flaky connections.
+//!
+//! 1. `POST /api/uploads` with `{ "length": <bytes> }` opens a session.
+//! 2. `PATCH /api/uploads/{id}` appends the request body at the offset given in
+//!    the `Upload-Offset` header. Whatever arrived before a dropped connection
+//!    is kept.
+//! 3. `GET`/`HEAD /api/uploads/{id}` reports the offset to resume from.
+//! 4. `POST /api/uploads/{id}/finalize` registers the picture once all bytes
+//!    are in, exactly like a regular upload.
+
+use std::io::SeekFrom;
+
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header},
+    response::IntoResponse,
+    routing,
+};
+use futures::StreamExt;
+use mime::Mime;
+use serde::Deserialize;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use crate::{
+    CONFIG,
+    common::{ApiError, ApiKey, ApiResult, AppState},
+    db::UploadSession,
+    ingest::{self, Ingested},
+    quota, uploads,
+};
+
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+
+#[derive(Deserialize)]
+struct CreateUpload {
+    length: u64,
+    content_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FinalizeQuery {
+    #[serde(default)]
+    allow_duplicate: bool,
+}
+
+pub fn upload_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/uploads", routing::post(create_upload))
+        .route(
+            "/api/uploads/{id}",
+            routing::get(get_upload)
+                .patch(patch_upload)
+                .delete(delete_upload),
+        )
+        .route("/api/uploads/{id}/finalize", routing::post(finalize_upload))
+}
+
+fn progress_headers(session: &UploadSession) -> [(HeaderName, HeaderValue); 2] {
+    [
+        (UPLOAD_OFFSET, HeaderValue::from(session.offset)),
+        (UPLOAD_LENGTH, HeaderValue::from(session.length)),
+    ]
+}
+
+async fn create_upload(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Json(req): Json<CreateUpload>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+    if req.length == 0 {
+        return Err(ApiError::BadRequest("length must be positive".into()));
+    }
+    if req.length > CONFIG.backend_max_upload_bytes as u64 {
+        return Err(ApiError::PayloadTooLarge(format!(
+            "uploads are limited to {} bytes",
+            CONFIG.backend_max_upload_bytes
+        )));
+    }
+    if let Some(ct) = &req.content_type {
+        ct.parse::<Mime>()
+            .map_err(|_| ApiError::UnsupportedMediaType(format!("invalid content type {ct}")))?;
+    }
+    quota::precheck(&state.repo, req.length).await?;
+
+    let session = state
+        .repo
+        .create_upload_session(req.length, req.content_type)
+        .await?;
+    if let Err(e) = uploads::create_part_file(&session.id).await {
+        state.repo.delete_upload_session(&session.id).await?;
+        return Err(ApiError::Internal(e.into()));
+    }
+    tracing::debug!(id = %session.id, length = session.length, "upload session created");
+
+    let location = HeaderValue::from_str(&format!("/api/uploads/{}", session.id))
+        .expect("uuid is a valid header value");
+    Ok((
+        StatusCode::CREATED,
+        [(header::LOCATION, location)],
+        progress_headers(&session),
+        Json(session),
+    ))
+}
+
+/// Also answers `HEAD`, which clients use to find the offset to resume from.
+async fn get_upload(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let session = state
+        .repo
+        .get_upload_session(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok((progress_headers(&session), Json(session)))
+}
+
+async fn patch_upload(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let _guard = uploads::try_claim(&id)
+        .ok_or_else(|| ApiError::Conflict("upload session is busy".into()))?;
+    let session = state
+        .repo
+        .get_upload_session(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let offset: u64 = headers
+        .get(&UPLOAD_OFFSET)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| ApiError::BadRequest("missing or invalid Upload-Offset header".into()))?;
+    if offset != session.offset {
+        return Err(ApiError::Conflict(format!(
+            "upload is at offset {}, not {offset}",
+            session.offset
+        )));
+    }
+
+    let path = uploads::part_path(&id);
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .await
+        .map_err(|e| ApiError::Internal(anyhow::Error::new(e).context(format!("open {path:?}"))))?;
+    // drop anything past the recorded offset, e.g. from a write we never confirmed
+    file.set_len(offset)
+        .await
+        .map_err(|e| ApiError::Internal(e.into()))?;
+    file.seek(SeekFrom::Start(offset))
+        .await
+        .map_err(|e| ApiError::Internal(e.into()))?;
+
+    let mut written = offset;
+    let mut failure = None;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let bytes = match chunk {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::warn!("upload {id} interrupted at {written}: {e}");
+                failure = Some(ApiError::BadRequest(format!("body stream failed: {e}")));
+                break;
+            }
+        };
+        let remaining = (session.length - written) as usize;
+        let take = bytes.len().min(remaining);
+        if let Err(e) = file.write_all(&bytes[..take]).await {
+            tracing::error!("write error on {path:?}: {e}");
+            failure = Some(ApiError::write_failed(e));
+            break;
+        }
+        written += take as u64;
+        if take < bytes.len() {
+            failure = Some(ApiError::PayloadTooLarge(format!(
+                "upload length is {} bytes",
+                session.length
+            )));
+            break;
+        }
+    }
+    file.flush().await.ok();
+    file.sync_all().await.ok();
+
+    // keep whatever made it to disk so the client can resume from there
+    state.repo.set_upload_offset(&id, written).await?;
+    if let Some(e) = failure {
+        return Err(e);
+    }
+
+    let session = UploadSession {
+        offset: written,
+        ..session
+    };
+    Ok((StatusCode::NO_CONTENT, progress_headers(&session)))
+}
+
+async fn finalize_upload(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<FinalizeQuery>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let _guard = uploads::try_claim(&id)
+        .ok_or_else(|| ApiError::Conflict("upload session is busy".into()))?;
+    let session = state
+        .repo
+        .get_upload_session(&id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if session.offset != session.length {
+        return Err(ApiError::Conflict(format!(
+            "upload incomplete: {} of {} bytes received",
+            session.offset, session.length
+        )));
+    }
+
+    let declared = session
+        .content_type
+        .as_deref()
+        .and_then(|ct| ct.parse().ok());
+    // `register` consumes the part file whatever the outcome, so the session ends here
+    let outcome = ingest::register(
+        &state.repo,
+        &state.settings,
+        &uploads::part_path(&id),
+        declared,
+        None,
+        query.allow_duplicate,
+    )
+    .await;
+    state.repo.delete_upload_session(&id).await?;
+
+    match outcome? {
+        Ingested::Created(saved) => Ok((StatusCode::CREATED, Json(saved))),
+        Ingested::Duplicate(existing) => Ok((StatusCode::OK, Json(existing))),
+    }
+}
+
+async fn delete_upload(
+    ApiKey { scope, .. }: ApiKey,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<impl IntoResponse> {
+    if scope != "rw" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let _guard = uploads::try_claim(&id)
+        .ok_or_else(|| ApiError::Conflict("upload session is busy".into()))?;
+    if !state.repo.delete_upload_session(&id).await? {
+        return Err(ApiError::NotFound);
+    }
+    uploads::remove_part_file(&id).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,374 @@
+//! Backup archives of everything needed to rebuild the frame: the database,
+//! every file in the data dir and the frame settings, plus a manifest with
+//! their checksums. Archives are written by `GET /api/admin/backup` and put
+//! back with `backend restore <archive>` while the backend is stopped.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail, ensure};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use libs::frame_settings::{FrameSettings, SharedSettings};
+
+use crate::{CONFIG, db::Repository, ingest};
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DB_ENTRY: &str = "picture_frame.db";
+const SETTINGS_ENTRY: &str = "frame_settings.toml";
+/// Archive folder holding the contents of the data dir.
+const DATA_PREFIX: &str = "data";
+const FORMAT_VERSION: u32 = 1;
+
+/// Written last, so a truncated archive fails to restore.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    created_at: i64,
+    files: Vec<ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct ManifestEntry {
+    path: String,
+    size: u64,
+    /// Hex-encoded SHA-256.
+    sha256: String,
+}
+
+/// Hashes what passes through it.
+struct Hashing<T> {
+    inner: T,
+    hasher: Sha256,
+    len: u64,
+}
+
+impl<T> Hashing<T> {
+    fn new(inner: T) -> Self {
+        Hashing {
+            inner,
+            hasher: Sha256::new(),
+            len: 0,
+        }
+    }
+
+    fn finish(self, path: String) -> ManifestEntry {
+        ManifestEntry {
+            path,
+            size: self.len,
+            sha256: format!("{:x}", self.hasher.finalize()),
+        }
+    }
+}
+
+impl<R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Hashing<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The database and settings as of one moment, ready to be archived with the
+/// data dir.
+pub struct Snapshot {
+    /// Copy of the database, removed on drop.
+    db: PathBuf,
+    settings: String,
+}
+
+/// Take a consistent copy of the database and the current settings.
+pub async fn snapshot(repo: &Repository, settings: &SharedSettings) -> Result<Snapshot> {
+    let db_file = Path::new(&CONFIG.backend_db_file);
+    let snapshot = Snapshot {
+        db: db_file.with_file_name(format!(".backup-{}.db", uuid::Uuid::new_v4())),
+        settings: toml::to_string_pretty(&settings.get().await)?,
+    };
+    repo.backup_database(snapshot.db.clone())
+        .await
+        .context("copying the database")?;
+    Ok(snapshot)
+}
+
+impl Snapshot {
+    /// Write the archive to `out`. Files that disappear from the data dir
+    /// while it is written are left out.
+    pub fn write(&self, out: impl Write) -> Result<()> {
+        let mut tar = tar::Builder::new(out);
+        let mut files = Vec::new();
+
+        files.push(append_file(&mut tar, DB_ENTRY.into(), &self.db)?);
+        files.push(append(
+            &mut tar,
+            SETTINGS_ENTRY.into(),
+            self.settings.as_bytes(),
+            self.settings.len() as u64,
+        )?);
+
+        let data_dir = Path::new(&CONFIG.backend_data_dir);
+        let walk = WalkDir::new(data_dir)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            // files still being received
+            .filter_entry(|e| e.depth() > 1 || e.file_name() != ingest::STAGING_DIR);
+        for entry in walk {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Some(name) = archive_name(entry.path().strip_prefix(data_dir)?) else {
+                tracing::warn!("skipping {}: not a UTF-8 name", entry.path().display());
+                continue;
+            };
+            match append_file(&mut tar, format!("{DATA_PREFIX}/{name}"), entry.path()) {
+                Ok(file) => files.push(file),
+                Err(e) if is_not_found(&e) => {
+                    tracing::debug!("{} went away during the backup", entry.path().display());
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let manifest = serde_json::to_vec_pretty(&Manifest {
+            version: FORMAT_VERSION,
+            created_at: chrono::Utc::now().timestamp(),
+            files,
+        })?;
+        let len = manifest.len() as u64;
+        append(&mut tar, MANIFEST_ENTRY.into(), manifest.as_slice(), len)?;
+        tar.into_inner()?.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.db)
+            && e.kind() != io::ErrorKind::NotFound
+        {
+            tracing::error!("failed to remove database copy {:?}: {e}", self.db);
+        }
+    }
+}
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
+
+/// `path` with `/` separators, if it is plain and valid UTF-8.
+fn archive_name(path: &Path) -> Option<String> {
+    let parts = path
+        .components()
+        .map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+fn append_file<W: Write>(
+    tar: &mut tar::Builder<W>,
+    name: String,
+    path: &Path,
+) -> Result<ManifestEntry> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    append(tar, name, file, len).with_context(|| format!("archiving {path:?}"))
+}
+
+fn append<W: Write>(
+    tar: &mut tar::Builder<W>,
+    name: String,
+    data: impl Read,
+    len: u64,
+) -> Result<ManifestEntry> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(len);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header.set_entry_type(tar::EntryType::Regular);
+    // never more than announced in the header, even if the file grows
+    let mut reader = Hashing::new(data.take(len));
+    tar.append_data(&mut header, &name, &mut reader)?;
+    ensure!(reader.len == len, "{name} shrank while it was archived");
+    Ok(reader.finish(name))
+}
+
+/// Replace the database, data dir and frame settings with the contents of
+/// `archive`, after checking every file against the manifest. The replaced
+/// state is moved aside rather than deleted; its location is returned.
+///
+/// The data dir, database and settings must be on one filesystem, as the
+/// swap is done by renaming.
+pub fn restore(archive: &Path) -> Result<PathBuf> {
+    let data_dir = PathBuf::from(&CONFIG.backend_data_dir);
+    let root = data_dir
+        .parent()
+        .context("data dir has no parent")?
+        .to_owned();
+    let staging = root.join(format!(".restore-{}", uuid::Uuid::new_v4()));
+
+    let unpacked = unpack(archive, &staging);
+    if let Err(e) = unpacked {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e.context(format!("{archive:?} cannot be restored")));
+    }
+
+    let aside = root.join(format!(
+        "pre-restore-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let swapped = swap(&staging, &aside, &data_dir);
+    let _ = std::fs::remove_dir_all(&staging);
+    swapped?;
+    Ok(aside)
+}
+
+/// Extract `archive` into `staging` and check it is complete and intact.
+fn unpack(archive: &Path, staging: &Path) -> Result<()> {
+    std::fs::create_dir_all(staging.join(DATA_PREFIX))?;
+    let file = File::open(archive).with_context(|| format!("cannot open {archive:?}"))?;
+    let mut tar = tar::Archive::new(file);
+
+    let mut manifest: Option<Manifest> = None;
+    let mut found = BTreeMap::new();
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type() != tar::EntryType::Regular {
+            bail!("unexpected {:?} entry", entry.header().entry_type());
+        }
+        let path = entry.path()?.into_owned();
+        let name = archive_name(&path).with_context(|| format!("unsafe entry {path:?}"))?;
+        if manifest.is_some() {
+            bail!("{name} follows the manifest");
+        }
+        let known = [MANIFEST_ENTRY, DB_ENTRY, SETTINGS_ENTRY].contains(&name.as_str())
+            || name.starts_with(&format!("{DATA_PREFIX}/"));
+        ensure!(known, "unexpected entry {name}");
+        if name == MANIFEST_ENTRY {
+            manifest = Some(serde_json::from_reader(&mut entry).context("reading the manifest")?);
+            continue;
+        }
+        let dest = staging.join(&path);
+        if let Some(dir) = dest.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut out = Hashing::new(File::create(&dest)?);
+        io::copy(&mut entry, &mut out).with_context(|| format!("extracting {name}"))?;
+        out.inner.sync_all()?;
+        found.insert(name.clone(), out.finish(name));
+    }
+
+    let manifest = manifest.context("the manifest is missing; the archive may be truncated")?;
+    ensure!(
+        manifest.version == FORMAT_VERSION,
+        "unsupported archive version {}",
+        manifest.version
+    );
+    for listed in &manifest.files {
+        match found.remove(&listed.path) {
+            Some(file) if file == *listed => {}
+            Some(_) => bail!("{} does not match its checksum", listed.path),
+            None => bail!("{} is missing", listed.path),
+        }
+    }
+    if let Some(extra) = found.keys().next() {
+        bail!("{extra} is not in the manifest");
+    }
+
+    let settings = std::fs::read_to_string(staging.join(SETTINGS_ENTRY))
+        .with_context(|| format!("{SETTINGS_ENTRY} is missing"))?;
+    toml::from_str::<FrameSettings>(&settings).context("invalid frame settings")?;
+    let db = staging.join(DB_ENTRY);
+    ensure!(db.is_file(), "{DB_ENTRY} is missing");
+    let conn = rusqlite::Connection::open(&db)?;
+    let check: String = conn.query_row("PRAGMA integrity_check", [], |r| r.get(0))?;
+    ensure!(check == "ok", "the database is damaged: {check}");
+    Ok(())
+}
+
+/// Move the current state into `aside` and the unpacked one into place. If a
+/// step fails, what was moved so far is put back.
+fn swap(staging: &Path, aside: &Path, data_dir: &Path) -> Result<()> {
+    let db_file = PathBuf::from(&CONFIG.backend_db_file);
+    let settings_file = PathBuf::from(&CONFIG.backend_frame_settings_file);
+    std::fs::create_dir_all(aside)?;
+
+    // (current location, name in `aside`, replacement)
+    let mut targets = vec![
+        (
+            data_dir.to_owned(),
+            DATA_PREFIX.to_owned(),
+            Some(staging.join(DATA_PREFIX)),
+        ),
+        (
+            db_file.clone(),
+            DB_ENTRY.to_owned(),
+            Some(staging.join(DB_ENTRY)),
+        ),
+        (
+            settings_file,
+            SETTINGS_ENTRY.to_owned(),
+            Some(staging.join(SETTINGS_ENTRY)),
+        ),
+    ];
+    // a leftover journal would be replayed against the restored database
+    for suffix in ["-journal", "-wal", "-shm"] {
+        let mut journal = db_file.clone().into_os_string();
+        journal.push(suffix);
+        targets.push((journal.into(), format!("{DB_ENTRY}{suffix}"), None));
+    }
+
+    let mut done: Vec<(&Path, PathBuf, bool)> = Vec::new();
+    let result = (|| -> Result<()> {
+        for (current, name, replacement) in &targets {
+            let kept = aside.join(name);
+            let moved = match std::fs::rename(current, &kept) {
+                Ok(()) => true,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+                Err(e) => return Err(e).with_context(|| format!("moving {current:?} aside")),
+            };
+            done.push((current, kept, moved));
+            if let Some(replacement) = replacement {
+                std::fs::rename(replacement, current)
+                    .with_context(|| format!("moving {replacement:?} to {current:?}"))?;
+            }
+        }
+        Ok(())
+    })();
+
+    if result.is_err() {
+        for (current, kept, moved) in done.into_iter().rev() {
+            if current.exists() {
+                let _ = std::fs::remove_dir_all(current).or_else(|_| std::fs::remove_file(current));
+            }
+            if moved && let Err(e) = std::fs::rename(&kept, current) {
+                tracing::error!("cannot move {kept:?} back to {current:?}: {e}");
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,30 @@
+//! Opaque pagination cursors: a hex-encoded JSON value, so clients cannot
+//! (and need not) interpret them.
+
+use std::fmt::Write;
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::ApiError;
+
+pub fn encode_cursor<T: Serialize>(value: &T) -> String {
+    let json = serde_json::to_vec(value).expect("cursor serializes");
+    json.iter()
+        .fold(String::with_capacity(json.len() * 2), |mut s, b| {
+            let _ = write!(s, "{b:02x}");
+            s
+        })
+}
+
+pub fn decode_cursor<T: DeserializeOwned>(cursor: &str) -> Result<T, ApiError> {
+    let invalid = || ApiError::BadRequest("invalid cursor".into());
+    if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid())?;
+    serde_json::from_slice(&bytes).map_err(|_| invalid())
+}
@@ -4,24 +4,64 @@ use axum::{
 };
 use thiserror::Error;
 
+use crate::media::validate::ValidationError;
+
 #[derive(Debug, Error)]
 pub enum ApiError {
     #[error("unauthorised")]
     Unauthorized,
+    #[error("forbidden")]
+    Forbidden,
     #[error("not found")]
     NotFound,
+    #[error("conflict: {0}")]
+    Conflict(String),
     #[error("bad request: {0}")]
     BadRequest(String),
+    #[error("payload too large: {0}")]
+    PayloadTooLarge(String),
+    #[error("unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+    #[error("unprocessable entity: {0}")]
+    Unprocessable(String),
+    /// The library is at its quota, or the disk is full.
+    #[error("insufficient storage: {0}")]
+    InsufficientStorage(String),
+    /// A server we fetched from on the client's behalf failed.
+    #[error("bad gateway: {0}")]
+    BadGateway(String),
     #[error(transparent)]
     Internal(#[from] anyhow::Error),
 }
 
+impl ApiError {
+    /// A failed write of an incoming file; a full disk is the client's to know.
+    pub fn write_failed(err: std::io::Error) -> Self {
+        if err.kind() == std::io::ErrorKind::StorageFull {
+            ApiError::InsufficientStorage("the disk is full".into())
+        } else {
+            ApiError::Internal(err.into())
+        }
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         match self {
             ApiError::Unauthorized => StatusCode::UNAUTHORIZED.into_response(),
+            ApiError::Forbidden => StatusCode::FORBIDDEN.into_response(),
             ApiError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg).into_response(),
             ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            ApiError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg).into_response(),
+            ApiError::UnsupportedMediaType(msg) => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, msg).into_response()
+            }
+            ApiError::Unprocessable(msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg).into_response(),
+            ApiError::InsufficientStorage(msg) => {
+                (StatusCode::INSUFFICIENT_STORAGE, msg).into_response()
+            }
+            ApiError::BadGateway(msg) => (StatusCode::BAD_GATEWAY, msg).into_response(),
             ApiError::Internal(err) => {
                 tracing::error!(error = ?err, "internal error");
                 StatusCode::INTERNAL_SERVER_ERROR.into_response()
@@ -29,3 +69,17 @@ impl IntoResponse for ApiError {
         }
     }
 }
+
+impl From<ValidationError> for ApiError {
+    fn from(err: ValidationError) -> Self {
+        match err {
+            ValidationError::UnknownFormat
+            | ValidationError::UnsupportedFormat(_)
+            | ValidationError::Mislabeled { .. } => ApiError::UnsupportedMediaType(err.to_string()),
+            ValidationError::TooLarge { .. } | ValidationError::Corrupt(_) => {
+                ApiError::Unprocessable(err.to_string())
+            }
+            ValidationError::Io(e) => ApiError::Internal(e.into()),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::{path::Path, time::UNIX_EPOCH};
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+/// Stream a file from disk.
+///
+/// `ServeFile` takes care of `Content-Type`, `Range` and `Last-Modified` /
+/// `If-Modified-Since`; on top of that we add a strong `ETag` derived from
+/// size and mtime and answer `If-None-Match` with `304 Not Modified`.
+pub async fn serve_file(path: &Path, mut req: Request) -> Response {
+    let meta = match tokio::fs::metadata(path).await {
+        Ok(m) if m.is_file() => m,
+        Ok(_) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            tracing::warn!("file missing on disk: {}", path.display());
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        Err(e) => {
+            tracing::error!("cannot stat {path:?}: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let etag = format!("\"{:x}-{:x}\"", meta.len(), mtime);
+    let etag_value = HeaderValue::from_str(&etag).expect("etag is ascii");
+
+    if let Some(inm) = req.headers().get(header::IF_NONE_MATCH) {
+        let matches = inm.to_str().is_ok_and(|v| {
+            v.split(',')
+                .map(|t| t.trim().trim_start_matches("W/"))
+                .any(|t| t == "*" || t == etag)
+        });
+        if matches {
+            return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag_value)]).into_response();
+        }
+        // RFC 9110 §13.1.3: If-Modified-Since is ignored when If-None-Match is present
+        req.headers_mut().remove(header::IF_MODIFIED_SINCE);
+    }
+
+    let mut res = match ServeFile::new(path).oneshot(req).await {
+        Ok(res) => res.map(Body::new),
+        Err(e) => match e {},
+    };
+    if res.status().is_success() || res.status() == StatusCode::NOT_MODIFIED {
+        res.headers_mut().insert(header::ETAG, etag_value);
+    }
+    res
+}
@@ -91,7 +91,7 @@ pub fn spawn_system_metrics(repo: Arc<Repository>) {
 }
 
 /// Recursively sum the sizes of all regular files under `path`.
-fn folder_size<P: AsRef<std::path::Path>>(path: P) -> u64 {
+pub fn folder_size<P: AsRef<std::path::Path>>(path: P) -> u64 {
     use walkdir::WalkDir;
     WalkDir::new(path)
         .into_iter()
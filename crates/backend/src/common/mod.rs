@@ -1,10 +1,14 @@
 mod auth;
+mod cursor;
 mod error;
+mod file_response;
 pub mod metrics;
 mod result;
 mod state;
 
 pub use auth::ApiKey;
+pub use cursor::{decode_cursor, encode_cursor};
 pub use error::ApiError;
+pub use file_response::serve_file;
 pub use result::ApiResult;
 pub use state::AppState;
@@ -2,10 +2,12 @@ use std::sync::Arc;
 
 use libs::frame_settings::SharedSettings;
 
-use crate::db::Repository;
+use crate::{db::Repository, fetch::Fetcher};
 
 #[derive(Clone)]
 pub struct AppState {
     pub repo: Arc<Repository>,
     pub settings: SharedSettings,
+    /// Downloads pictures for URL imports.
+    pub fetcher: Arc<Fetcher>,
 }
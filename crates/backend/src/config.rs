@@ -10,9 +10,107 @@ pub struct Config {
     pub backend_data_dir: String,
     pub backend_db_file: String,
     pub backend_frame_settings_file: String,
+    /// Albums and other library data published by the backend for the display.
+    #[serde(default = "default_library_index_file")]
+    pub backend_library_index_file: String,
     pub prometheus_port: String,
     pub prometheus_ipv4_address: String,
     pub prometheus_refresh_interval: u64,
+    #[serde(default = "default_max_image_dimension")]
+    pub backend_max_image_width: u32,
+    #[serde(default = "default_max_image_dimension")]
+    pub backend_max_image_height: u32,
+    /// Limit for a whole (batch) upload request.
+    #[serde(default = "default_max_upload_bytes")]
+    pub backend_max_upload_bytes: usize,
+    /// Resumable upload sessions idle for longer than this are discarded.
+    #[serde(default = "default_upload_session_ttl_secs")]
+    pub backend_upload_session_ttl_secs: u64,
+    /// Bake orientation, strip GPS/serials and cap resolution on upload.
+    #[serde(default)]
+    pub backend_normalize_uploads: bool,
+    #[serde(default = "default_normalize_max_edge")]
+    pub backend_normalize_max_edge: u32,
+    /// Keep the untouched upload next to the normalized picture.
+    #[serde(default)]
+    pub backend_keep_originals: bool,
+    /// Trashed pictures are deleted for good after this many days.
+    #[serde(default = "default_trash_retention_days")]
+    pub backend_trash_retention_days: u64,
+    /// Folder watched for pictures to import, e.g. copied over SSH; unset to
+    /// disable.
+    #[serde(default)]
+    pub backend_ingest_dir: Option<String>,
+    /// A file in the ingest folder is imported once it stopped changing for
+    /// this long.
+    #[serde(default = "default_ingest_settle_secs")]
+    pub backend_ingest_settle_secs: u64,
+    /// Limit for a picture imported from a URL.
+    #[serde(default = "default_import_max_bytes")]
+    pub backend_import_max_bytes: u64,
+    #[serde(default = "default_import_timeout_secs")]
+    pub backend_import_timeout_secs: u64,
+    /// Let imports reach loopback, private and link-local addresses, i.e. the
+    /// host itself and the local network.
+    #[serde(default)]
+    pub backend_import_allow_private: bool,
+    /// Most the data dir may hold, in bytes; unlimited if unset.
+    #[serde(default)]
+    pub backend_quota_bytes: Option<u64>,
+    /// Most pictures the library may hold; unlimited if unset.
+    #[serde(default)]
+    pub backend_quota_pictures: Option<u64>,
+    /// Make room for new pictures by deleting the oldest ones that are neither
+    /// pinned nor favorites, instead of rejecting them.
+    #[serde(default)]
+    pub backend_quota_evict: bool,
+    /// How often the library is checked for consistency in the background,
+    /// which decodes every picture; 0 disables the check.
+    #[serde(default = "default_fsck_interval_hours")]
+    pub backend_fsck_interval_hours: u64,
+    /// Port of the WebDAV listener, bound next to the API; unset to disable.
+    #[serde(default)]
+    pub backend_webdav_port: Option<u16>,
+}
+
+fn default_max_image_dimension() -> u32 {
+    12_000
+}
+
+fn default_max_upload_bytes() -> usize {
+    512 * 1024 * 1024 // 512MiB
+}
+
+fn default_upload_session_ttl_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_normalize_max_edge() -> u32 {
+    2048 // what the display scales down to anyway
+}
+
+fn default_trash_retention_days() -> u64 {
+    30
+}
+
+fn default_import_max_bytes() -> u64 {
+    64 * 1024 * 1024 // 64MiB
+}
+
+fn default_import_timeout_secs() -> u64 {
+    60
+}
+
+fn default_ingest_settle_secs() -> u64 {
+    5
+}
+
+fn default_fsck_interval_hours() -> u64 {
+    24
+}
+
+fn default_library_index_file() -> String {
+    "library.toml".into()
 }
 
 pub static CONFIG: Lazy<Config> = Lazy::new(|| {
@@ -33,6 +131,14 @@ pub static CONFIG: Lazy<Config> = Lazy::new(|| {
         .join(&config.backend_frame_settings_file)
         .to_string_lossy()
         .into_owned();
+    let backend_library_index_file = config_dir
+        .join(&config.backend_library_index_file)
+        .to_string_lossy()
+        .into_owned();
+    let backend_ingest_dir = config
+        .backend_ingest_dir
+        .as_ref()
+        .map(|dir| config_dir.join(dir).to_string_lossy().into_owned());
 
     std::fs::create_dir_all(&backend_data_dir).expect("Failed to create data directory");
 
@@ -40,6 +146,8 @@ pub static CONFIG: Lazy<Config> = Lazy::new(|| {
     config.backend_data_dir = backend_data_dir;
     config.backend_db_file = backend_db_file;
     config.backend_frame_settings_file = backend_frame_settings_file;
+    config.backend_library_index_file = backend_library_index_file;
+    config.backend_ingest_dir = backend_ingest_dir;
 
     config
 });
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// A named collection of pictures; a picture can be in any number of albums.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Album {
+    pub id: String,
+    pub name: String,
+    pub created_at: i64,
+    pub picture_count: u64,
+}
@@ -0,0 +1,14 @@
+use serde::Serialize;
+
+/// What a bulk operation did to one picture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOutcome {
+    Done,
+    /// The picture already was in the requested state.
+    Unchanged,
+    /// No such picture outside the trash.
+    NotFound,
+    /// Left as it was because of an error outside the database.
+    Failed,
+}
@@ -1,5 +1,16 @@
+mod album;
+mod bulk;
 mod picture;
 mod repository;
+mod tag;
+mod upload_session;
 
-pub use picture::Picture;
-pub use repository::Repository;
+pub use album::Album;
+pub use bulk::BulkOutcome;
+pub use picture::{
+    PageStart, Picture, PictureEdit, PictureFilter, PictureMetadata, PictureSort, SortKey,
+    SortValue,
+};
+pub use repository::{Repository, is_unique_violation};
+pub use tag::{Tag, normalize_tag};
+pub use upload_session::UploadSession;
@@ -1,8 +1,144 @@
 use serde::{Deserialize, Serialize};
 
+use libs::{
+    frame_settings::TagFilter,
+    framing::{Crop, FocalPoint, Rotation},
+    schedule::ScheduleRule,
+};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Picture {
     pub id: String,
     pub filename: String,
     pub added_at: i64,
+    /// Hex-encoded SHA-256 of the file as uploaded, before normalization;
+    /// `None` until backfilled for older rows. Rows backfilled after their
+    /// original was dropped hash the stored file instead.
+    pub content_hash: Option<String>,
+    #[serde(flatten)]
+    pub metadata: PictureMetadata,
+    pub title: Option<String>,
+    /// Shown on the display when captions are enabled.
+    pub caption: Option<String>,
+    pub favorite: bool,
+    /// How often the picture comes up relative to others when the display
+    /// shuffles by weight; 1 unless raised.
+    pub weight: u32,
+    /// When the display may show the picture; always if empty.
+    #[serde(default)]
+    pub schedule: Vec<ScheduleRule>,
+    /// Part of the picture the display shows; all of it if `None`.
+    pub crop: Option<Crop>,
+    /// Kept in view when the display fills the screen; the center if `None`.
+    pub focal_point: Option<FocalPoint>,
+    /// Manual correction on top of the EXIF orientation.
+    pub rotation: Rotation,
+    /// Where the picture was imported from, if it was fetched from a URL.
+    pub source_url: Option<String>,
+    /// Sorted.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// When the picture was moved to the trash (unix millis); `None` if it wasn't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<i64>,
+}
+
+/// Changes to the editable fields of a picture; `None` leaves a field as it is.
+#[derive(Debug, Clone, Default)]
+pub struct PictureEdit {
+    pub title: Option<Option<String>>,
+    pub caption: Option<Option<String>>,
+    pub favorite: Option<bool>,
+    pub weight: Option<u32>,
+    pub schedule: Option<Vec<ScheduleRule>>,
+    pub crop: Option<Option<Crop>>,
+    pub focal_point: Option<Option<FocalPoint>>,
+}
+
+/// Narrows down which pictures a listing returns. Date bounds are unix
+/// millis; lower bounds are inclusive, upper bounds exclusive.
+#[derive(Debug, Clone, Default)]
+pub struct PictureFilter {
+    pub tags: TagFilter,
+    pub added_after: Option<i64>,
+    pub added_before: Option<i64>,
+    pub taken_after: Option<i64>,
+    pub taken_before: Option<i64>,
+    pub favorite: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    AddedAt,
+    /// Pictures without a capture time sort by when they were added.
+    TakenAt,
+    Filename,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PictureSort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Default for PictureSort {
+    fn default() -> Self {
+        PictureSort {
+            key: SortKey::AddedAt,
+            descending: true,
+        }
+    }
+}
+
+/// Value a picture is sorted by.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SortValue {
+    Int(i64),
+    Text(String),
+}
+
+impl SortKey {
+    pub(super) fn expr(self) -> &'static str {
+        match self {
+            SortKey::AddedAt => "pictures.added_at",
+            SortKey::TakenAt => "COALESCE(pictures.taken_at, pictures.added_at)",
+            SortKey::Filename => "pictures.filename",
+        }
+    }
+
+    pub fn value_of(self, picture: &Picture) -> SortValue {
+        match self {
+            SortKey::AddedAt => SortValue::Int(picture.added_at),
+            SortKey::TakenAt => {
+                SortValue::Int(picture.metadata.taken_at.unwrap_or(picture.added_at))
+            }
+            SortKey::Filename => SortValue::Text(picture.filename.clone()),
+        }
+    }
+}
+
+/// Where a page starts: right after the picture with this sort value and id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageStart {
+    pub value: SortValue,
+    pub id: String,
+}
+
+/// What we know about the stored file from its header and EXIF.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PictureMetadata {
+    /// Capture time in unix millis; EXIF times without an offset are taken as UTC.
+    pub taken_at: Option<i64>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    /// Pixel size of the stored file, before `orientation` is applied.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// EXIF orientation, 1-8.
+    pub orientation: Option<u16>,
+    pub gps_latitude: Option<f64>,
+    pub gps_longitude: Option<f64>,
 }
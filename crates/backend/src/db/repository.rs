@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use anyhow::Result;
 use argon2::{
@@ -7,10 +7,236 @@ use argon2::{
 };
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{OptionalExtension, params};
+use rusqlite::{
+    Connection, DatabaseName, OptionalExtension, Row, Transaction, params, params_from_iter,
+    types::{Type, Value},
+};
 use tokio::task;
 
-use super::Picture;
+use libs::{
+    framing::{Crop, FocalPoint, Rotation},
+    schedule::ScheduleRule,
+};
+
+use super::{
+    Album, BulkOutcome, PageStart, Picture, PictureEdit, PictureFilter, PictureMetadata,
+    PictureSort, SortValue, Tag, UploadSession,
+};
+
+/// Columns selected for a `Picture`, in the order `picture_from_row` reads them.
+/// Qualified, so they can be selected from joins.
+const PICTURE_COLUMNS: &str = "pictures.id, pictures.filename, pictures.added_at, \
+     pictures.content_hash, pictures.taken_at, pictures.camera_make, pictures.camera_model, \
+     pictures.width, pictures.height, pictures.orientation, pictures.gps_latitude, \
+     pictures.gps_longitude, pictures.title, pictures.caption, pictures.deleted_at, \
+     pictures.favorite, pictures.weight, pictures.schedule, \
+     pictures.crop_x, pictures.crop_y, pictures.crop_width, pictures.crop_height, \
+     pictures.focal_x, pictures.focal_y, pictures.rotation, pictures.flipped, \
+     pictures.source_url, \
+     (SELECT group_concat(t.name, ',') FROM picture_tags pt JOIN tags t ON t.id = pt.tag_id \
+      WHERE pt.picture_id = pictures.id)";
+
+fn picture_from_row(row: &Row<'_>) -> rusqlite::Result<Picture> {
+    Ok(Picture {
+        id: row.get(0)?,
+        filename: row.get(1)?,
+        added_at: row.get(2)?,
+        content_hash: row.get(3)?,
+        metadata: PictureMetadata {
+            taken_at: row.get(4)?,
+            camera_make: row.get(5)?,
+            camera_model: row.get(6)?,
+            width: row.get(7)?,
+            height: row.get(8)?,
+            orientation: row.get(9)?,
+            gps_latitude: row.get(10)?,
+            gps_longitude: row.get(11)?,
+        },
+        title: row.get(12)?,
+        caption: row.get(13)?,
+        deleted_at: row.get(14)?,
+        favorite: row.get(15)?,
+        weight: row.get(16)?,
+        schedule: schedule_from_sql(row, 17)?,
+        crop: crop_from_sql(row, 18)?,
+        focal_point: focal_point_from_sql(row, 22)?,
+        rotation: Rotation {
+            degrees: row.get(24)?,
+            flip: row.get(25)?,
+        },
+        source_url: row.get(26)?,
+        tags: {
+            let joined: Option<String> = row.get(27)?;
+            let mut tags: Vec<String> = joined
+                .iter()
+                .flat_map(|j| j.split(','))
+                .map(str::to_owned)
+                .collect();
+            tags.sort();
+            tags
+        },
+    })
+}
+
+/// Schedules are stored as a JSON array, `NULL` when empty.
+fn schedule_from_sql(row: &Row<'_>, idx: usize) -> rusqlite::Result<Vec<ScheduleRule>> {
+    let Some(json) = row.get::<_, Option<String>>(idx)? else {
+        return Ok(Vec::new());
+    };
+    serde_json::from_str(&json)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e)))
+}
+
+fn schedule_to_sql(rules: &[ScheduleRule]) -> Result<Option<String>> {
+    if rules.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(rules)?))
+}
+
+/// Reads a crop from four consecutive columns, all `NULL` when there is none.
+fn crop_from_sql(row: &Row<'_>, idx: usize) -> rusqlite::Result<Option<Crop>> {
+    let Some(x) = row.get(idx)? else {
+        return Ok(None);
+    };
+    Ok(Some(Crop {
+        x,
+        y: row.get(idx + 1)?,
+        width: row.get(idx + 2)?,
+        height: row.get(idx + 3)?,
+    }))
+}
+
+/// Reads a focal point from two consecutive columns.
+fn focal_point_from_sql(row: &Row<'_>, idx: usize) -> rusqlite::Result<Option<FocalPoint>> {
+    let Some(x) = row.get(idx)? else {
+        return Ok(None);
+    };
+    Ok(Some(FocalPoint {
+        x,
+        y: row.get(idx + 1)?,
+    }))
+}
+
+/// Matches pictures carrying tag `?` (or, with `IN (…)`, one of several).
+const HAS_TAG: &str = "SELECT 1 FROM picture_tags pt JOIN tags t ON t.id = pt.tag_id \
+     WHERE pt.picture_id = pictures.id AND t.name";
+
+/// `WHERE` clause and its parameters for a [`PictureFilter`]; trashed
+/// pictures never match.
+fn picture_filter_sql(filter: &PictureFilter) -> (String, Vec<Value>) {
+    let placeholders = |n: usize| vec!["?"; n].join(", ");
+    let mut clauses = vec!["pictures.deleted_at IS NULL".to_owned()];
+    let mut params = Vec::new();
+
+    let tags = &filter.tags;
+    for tag in &tags.all {
+        clauses.push(format!("EXISTS ({HAS_TAG} = ?)"));
+        params.push(Value::Text(tag.clone()));
+    }
+    if !tags.any.is_empty() {
+        clauses.push(format!(
+            "EXISTS ({HAS_TAG} IN ({}))",
+            placeholders(tags.any.len())
+        ));
+        params.extend(tags.any.iter().cloned().map(Value::Text));
+    }
+    if !tags.exclude.is_empty() {
+        clauses.push(format!(
+            "NOT EXISTS ({HAS_TAG} IN ({}))",
+            placeholders(tags.exclude.len())
+        ));
+        params.extend(tags.exclude.iter().cloned().map(Value::Text));
+    }
+
+    // pictures without a capture time never match a `taken_*` bound
+    for (bound, cond) in [
+        (filter.added_after, "pictures.added_at >= ?"),
+        (filter.added_before, "pictures.added_at < ?"),
+        (filter.taken_after, "pictures.taken_at >= ?"),
+        (filter.taken_before, "pictures.taken_at < ?"),
+    ] {
+        if let Some(bound) = bound {
+            clauses.push(cond.to_owned());
+            params.push(Value::Integer(bound));
+        }
+    }
+    if let Some(favorite) = filter.favorite {
+        clauses.push("pictures.favorite = ?".to_owned());
+        params.push(Value::Integer(favorite.into()));
+    }
+
+    (clauses.join(" AND "), params)
+}
+
+/// Add a column to an existing table unless it is already there.
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let exists = conn
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"
+        ))?
+        .exists([column])?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl};"))?;
+    }
+    Ok(())
+}
+
+/// Whether `err` comes from a `UNIQUE` constraint, e.g. a taken album name.
+pub fn is_unique_violation(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<rusqlite::Error>(),
+        Some(rusqlite::Error::SqliteFailure(e, _))
+            if e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE
+    )
+}
+
+const ALBUM_SELECT: &str = r#"
+    SELECT a.id, a.name, a.created_at, COUNT(p.id)
+    FROM albums a
+    LEFT JOIN album_pictures ap ON ap.album_id = a.id
+    LEFT JOIN pictures p ON p.id = ap.picture_id AND p.deleted_at IS NULL
+"#;
+
+fn album_from_row(row: &Row<'_>) -> rusqlite::Result<Album> {
+    Ok(Album {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        created_at: row.get(2)?,
+        picture_count: row.get(3)?,
+    })
+}
+
+/// Run `op` for every id within `tx`, stopping at the first error.
+fn for_each_id<T>(
+    tx: &Transaction<'_>,
+    ids: &[String],
+    mut op: impl FnMut(&Transaction<'_>, &str) -> rusqlite::Result<T>,
+) -> rusqlite::Result<Vec<T>> {
+    ids.iter().map(|id| op(tx, id)).collect()
+}
+
+/// Drop tags no picture carries any more.
+fn prune_tags(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute(
+        "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM picture_tags)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn is_live(tx: &Transaction<'_>, id: &str) -> rusqlite::Result<bool> {
+    tx.prepare_cached("SELECT 1 FROM pictures WHERE id = ?1 AND deleted_at IS NULL")?
+        .exists([id])
+}
+
+fn changed(n: usize) -> BulkOutcome {
+    if n > 0 {
+        BulkOutcome::Done
+    } else {
+        BulkOutcome::Unchanged
+    }
+}
 
 pub struct Repository {
     pool: Arc<Pool<SqliteConnectionManager>>,
@@ -28,9 +254,21 @@ impl Repository {
         conn.execute_batch(
             r#"
             CREATE TABLE IF NOT EXISTS pictures (
-               id        TEXT PRIMARY KEY,
-               filename  TEXT NOT NULL,
-               added_at  INTEGER NOT NULL
+               id             TEXT PRIMARY KEY,
+               filename       TEXT NOT NULL,
+               added_at       INTEGER NOT NULL,
+               content_hash   TEXT,
+               taken_at       INTEGER,
+               camera_make    TEXT,
+               camera_model   TEXT,
+               width          INTEGER,
+               height         INTEGER,
+               orientation    INTEGER,
+               gps_latitude   REAL,
+               gps_longitude  REAL,
+               title          TEXT,
+               caption        TEXT,
+               deleted_at     INTEGER
             );
 
             CREATE TABLE IF NOT EXISTS api_keys (
@@ -39,6 +277,97 @@ impl Repository {
                 scope       TEXT NOT NULL,
                 created_at  INTEGER NOT NULL
             );
+
+            CREATE TABLE IF NOT EXISTS upload_sessions (
+                id            TEXT PRIMARY KEY,
+                length        INTEGER NOT NULL,
+                offset        INTEGER NOT NULL DEFAULT 0,
+                content_type  TEXT,
+                created_at    INTEGER NOT NULL,
+                updated_at    INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS albums (
+                id          TEXT PRIMARY KEY,
+                name        TEXT NOT NULL UNIQUE,
+                created_at  INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS album_pictures (
+                album_id    TEXT NOT NULL REFERENCES albums (id) ON DELETE CASCADE,
+                picture_id  TEXT NOT NULL REFERENCES pictures (id) ON DELETE CASCADE,
+                added_at    INTEGER NOT NULL,
+                PRIMARY KEY (album_id, picture_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS tags (
+                id    INTEGER PRIMARY KEY,
+                name  TEXT NOT NULL UNIQUE
+            );
+
+            CREATE TABLE IF NOT EXISTS picture_tags (
+                picture_id  TEXT NOT NULL REFERENCES pictures (id) ON DELETE CASCADE,
+                tag_id      INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+                PRIMARY KEY (picture_id, tag_id)
+            );
+            "#,
+        )?;
+
+        // columns added after the first release
+        for (column, decl) in [
+            ("content_hash", "TEXT"),
+            ("taken_at", "INTEGER"),
+            ("camera_make", "TEXT"),
+            ("camera_model", "TEXT"),
+            ("width", "INTEGER"),
+            ("height", "INTEGER"),
+            ("orientation", "INTEGER"),
+            ("gps_latitude", "REAL"),
+            ("gps_longitude", "REAL"),
+            ("title", "TEXT"),
+            ("caption", "TEXT"),
+            ("deleted_at", "INTEGER"),
+            ("favorite", "INTEGER NOT NULL DEFAULT 0"),
+            ("weight", "INTEGER NOT NULL DEFAULT 1"),
+            ("schedule", "TEXT"),
+            ("crop_x", "REAL"),
+            ("crop_y", "REAL"),
+            ("crop_width", "REAL"),
+            ("crop_height", "REAL"),
+            ("focal_x", "REAL"),
+            ("focal_y", "REAL"),
+            ("rotation", "INTEGER NOT NULL DEFAULT 0"),
+            ("flipped", "INTEGER NOT NULL DEFAULT 0"),
+            ("source_url", "TEXT"),
+            ("duplicate_of", "TEXT"),
+        ] {
+            ensure_column(&conn, "pictures", column, decl)?;
+        }
+
+        // copies kept on purpose point at the first picture with their content,
+        // so the hash stays unique among the others
+        conn.execute_batch(
+            r#"
+            UPDATE pictures AS d
+            SET duplicate_of = (
+                SELECT p.id FROM pictures p
+                WHERE p.content_hash = d.content_hash AND p.deleted_at IS NULL
+                ORDER BY p.added_at, p.id
+                LIMIT 1
+            )
+            WHERE d.content_hash IS NOT NULL AND d.deleted_at IS NULL AND d.duplicate_of IS NULL
+              AND d.id != (
+                SELECT p.id FROM pictures p
+                WHERE p.content_hash = d.content_hash AND p.deleted_at IS NULL
+                ORDER BY p.added_at, p.id
+                LIMIT 1
+              );
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_pictures_unique_content_hash ON pictures (content_hash)
+                WHERE deleted_at IS NULL AND duplicate_of IS NULL;
+            CREATE INDEX IF NOT EXISTS idx_pictures_content_hash ON pictures (content_hash);
+            CREATE INDEX IF NOT EXISTS idx_album_pictures_picture ON album_pictures (picture_id);
+            CREATE INDEX IF NOT EXISTS idx_picture_tags_tag ON picture_tags (tag_id);
+            CREATE INDEX IF NOT EXISTS idx_pictures_deleted_at ON pictures (deleted_at);
             "#,
         )?;
         Ok(())
@@ -48,32 +377,74 @@ impl Repository {
         let pool = self.pool.clone();
         tokio::task::spawn_blocking(move || {
             let conn = pool.get()?;
-            let n: usize = conn.query_row("SELECT COUNT(*) FROM pictures", [], |r| r.get(0))?;
+            let n: usize = conn.query_row(
+                "SELECT COUNT(*) FROM pictures WHERE deleted_at IS NULL",
+                [],
+                |r| r.get(0),
+            )?;
             Ok(n)
         })
         .await?
     }
 
-    pub async fn list_pictures(&self) -> Result<Vec<Picture>> {
+    /// Number of pictures matching `filter`.
+    pub async fn count_matching_pictures(&self, filter: &PictureFilter) -> Result<u64> {
         let pool = self.pool.clone();
+        let (where_sql, params) = picture_filter_sql(filter);
         task::spawn_blocking(move || {
             let conn = pool.get()?;
-            let mut stmt = conn.prepare(
+            let n = conn.query_row(
+                &format!("SELECT COUNT(*) FROM pictures WHERE {where_sql}"),
+                params_from_iter(params),
+                |r| r.get(0),
+            )?;
+            Ok(n)
+        })
+        .await?
+    }
+
+    /// Pictures matching `filter` in `sort` order, starting after `start` and
+    /// at most `limit` of them. Ties are broken by id.
+    pub async fn list_pictures(
+        &self,
+        filter: &PictureFilter,
+        sort: PictureSort,
+        start: Option<PageStart>,
+        limit: Option<u32>,
+    ) -> Result<Vec<Picture>> {
+        let pool = self.pool.clone();
+        let (mut where_sql, mut params) = picture_filter_sql(filter);
+        let key = sort.key.expr();
+        let (dir, cmp) = if sort.descending {
+            ("DESC", "<")
+        } else {
+            ("ASC", ">")
+        };
+        if let Some(start) = start {
+            where_sql.push_str(&format!(
+                " AND ({key} {cmp} ? OR ({key} = ? AND pictures.id {cmp} ?))"
+            ));
+            let value = match start.value {
+                SortValue::Int(v) => Value::Integer(v),
+                SortValue::Text(v) => Value::Text(v),
+            };
+            params.extend([value.clone(), value, Value::Text(start.id)]);
+        }
+        let limit_sql = limit.map(|n| format!("LIMIT {n}")).unwrap_or_default();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(&format!(
                 r#"
-                SELECT id, filename, added_at
+                SELECT {PICTURE_COLUMNS}
                 FROM pictures
-                ORDER BY added_at DESC
-                "#,
-            )?;
+                WHERE {where_sql}
+                ORDER BY {key} {dir}, pictures.id {dir}
+                {limit_sql}
+                "#
+            ))?;
 
             let iter = stmt
-                .query_map([], |row| {
-                    Ok(Picture {
-                        id: row.get(0)?,
-                        filename: row.get(1)?,
-                        added_at: row.get(2)?,
-                    })
-                })?
+                .query_map(params_from_iter(params), picture_from_row)?
                 .collect::<Result<Vec<_>, _>>()?;
 
             Ok(iter)
@@ -81,9 +452,20 @@ impl Repository {
         .await?
     }
 
-    pub async fn add_picture(&self, filename: &str) -> Result<Picture> {
+    /// Insert a new picture. A second live picture with the same content
+    /// violates a `UNIQUE` constraint unless `allow_duplicate` is set.
+    pub async fn add_picture(
+        &self,
+        filename: &str,
+        content_hash: &str,
+        metadata: PictureMetadata,
+        source_url: Option<&str>,
+        allow_duplicate: bool,
+    ) -> Result<Picture> {
         let pool = self.pool.clone();
         let filename = filename.to_owned();
+        let content_hash = content_hash.to_owned();
+        let source_url = source_url.map(str::to_owned);
         task::spawn_blocking(move || {
             let conn = pool.get()?;
 
@@ -91,38 +473,169 @@ impl Repository {
                 id: uuid::Uuid::new_v4().to_string(),
                 filename,
                 added_at: chrono::Utc::now().timestamp_millis(),
+                content_hash: Some(content_hash),
+                metadata,
+                title: None,
+                caption: None,
+                favorite: false,
+                weight: 1,
+                schedule: Vec::new(),
+                crop: None,
+                focal_point: None,
+                rotation: Rotation::default(),
+                source_url,
+                tags: Vec::new(),
+                deleted_at: None,
             };
 
+            let m = &dto.metadata;
             conn.execute(
                 r#"
-                INSERT INTO pictures (id, filename, added_at)
-                VALUES (?1, ?2, ?3)
+                INSERT INTO pictures (id, filename, added_at, content_hash, taken_at, camera_make,
+                                      camera_model, width, height, orientation, gps_latitude,
+                                      gps_longitude, source_url, duplicate_of)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13,
+                        CASE WHEN ?14 THEN (
+                            SELECT id FROM pictures
+                            WHERE content_hash = ?4 AND deleted_at IS NULL AND duplicate_of IS NULL
+                        ) END)
                 "#,
-                params![dto.id, dto.filename, dto.added_at],
+                params![
+                    dto.id,
+                    dto.filename,
+                    dto.added_at,
+                    dto.content_hash,
+                    m.taken_at,
+                    m.camera_make,
+                    m.camera_model,
+                    m.width,
+                    m.height,
+                    m.orientation,
+                    m.gps_latitude,
+                    m.gps_longitude,
+                    dto.source_url,
+                    allow_duplicate
+                ],
             )?;
             Ok(dto)
         })
         .await?
     }
 
-    /// Returns the filename and deletes the DB row in one round-trip.
-    pub async fn delete_picture_and_return_filename(
-        &self,
-        id: &str,
-    ) -> anyhow::Result<Option<String>> {
+    /// Move a picture to the trash and return its filename; `None` if there
+    /// is no such picture outside the trash.
+    pub async fn trash_picture(&self, id: &str) -> Result<Option<String>> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.query_row(
+                r#"
+                UPDATE pictures SET deleted_at = ?2
+                WHERE id = ?1 AND deleted_at IS NULL
+                RETURNING filename
+                "#,
+                params![id, chrono::Utc::now().timestamp_millis()],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await?
+    }
+
+    /// Returns whether the picture was in the trash.
+    pub async fn restore_picture(&self, id: &str) -> Result<bool> {
+        let pool = self.pool.clone();
         let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            // content uploaded again while this one was in the trash stays the original
+            let n = conn.execute(
+                r#"
+                UPDATE pictures
+                SET deleted_at = NULL,
+                    duplicate_of = (
+                        SELECT p.id FROM pictures p
+                        WHERE p.content_hash = pictures.content_hash AND p.deleted_at IS NULL
+                          AND p.duplicate_of IS NULL
+                    )
+                WHERE id = ?1 AND deleted_at IS NOT NULL
+                "#,
+                [&id],
+            )?;
+            Ok(n > 0)
+        })
+        .await?
+    }
+
+    /// Trashed pictures, most recently deleted first.
+    pub async fn list_trashed_pictures(&self) -> Result<Vec<Picture>> {
         let pool = self.pool.clone();
-        tokio::task::spawn_blocking(move || {
+        task::spawn_blocking(move || {
             let conn = pool.get()?;
-            let fname: Option<String> = conn
-                .query_row("SELECT filename FROM pictures WHERE id = ?1", [&id], |r| {
-                    r.get(0)
-                })
-                .optional()?;
-            if let Some(ref _f) = fname {
-                conn.execute("DELETE FROM pictures WHERE id = ?1", [&id])?;
-            }
-            Ok(fname)
+            let mut stmt = conn.prepare(&format!(
+                r#"
+                SELECT {PICTURE_COLUMNS}
+                FROM pictures
+                WHERE deleted_at IS NOT NULL
+                ORDER BY deleted_at DESC
+                "#
+            ))?;
+            let pics = stmt
+                .query_map([], picture_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(pics)
+        })
+        .await?
+    }
+
+    pub async fn get_trashed_picture(&self, id: &str) -> Result<Option<Picture>> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.query_row(
+                &format!(
+                    "SELECT {PICTURE_COLUMNS} FROM pictures WHERE id = ?1 AND deleted_at IS NOT NULL"
+                ),
+                params![id],
+                picture_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await?
+    }
+
+    /// Ids of pictures trashed before `cutoff` (unix millis).
+    pub async fn list_trashed_before(&self, cutoff: i64) -> Result<Vec<String>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare("SELECT id FROM pictures WHERE deleted_at < ?1")?;
+            let ids = stmt
+                .query_map([cutoff], |r| r.get(0))?
+                .collect::<Result<Vec<String>, _>>()?;
+            Ok(ids)
+        })
+        .await?
+    }
+
+    /// Delete a trashed picture's row for good and return its filename;
+    /// `None` if it is not in the trash (any more).
+    pub async fn purge_picture(&self, id: &str) -> Result<Option<String>> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.query_row(
+                "DELETE FROM pictures WHERE id = ?1 AND deleted_at IS NOT NULL RETURNING filename",
+                [&id],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
         })
         .await?
     }
@@ -133,25 +646,968 @@ impl Repository {
         task::spawn_blocking(move || {
             let conn = pool.get()?;
             conn.query_row(
-                r#"
-                SELECT id, filename, added_at
-                FROM pictures
-                WHERE id = ?1
-                "#,
+                &format!(
+                    r#"
+                    SELECT {PICTURE_COLUMNS}
+                    FROM pictures
+                    WHERE id = ?1 AND deleted_at IS NULL
+                    "#
+                ),
                 params![id],
-                |row| {
-                    Ok(Picture {
-                        id: row.get(0)?,
-                        filename: row.get(1)?,
-                        added_at: row.get(2)?,
-                    })
-                },
+                picture_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await?
+    }
+
+    /// Oldest picture whose file has the given SHA-256, if any.
+    pub async fn find_picture_by_hash(&self, content_hash: &str) -> Result<Option<Picture>> {
+        let pool = self.pool.clone();
+        let content_hash = content_hash.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.query_row(
+                &format!(
+                    r#"
+                    SELECT {PICTURE_COLUMNS}
+                    FROM pictures
+                    WHERE content_hash = ?1 AND deleted_at IS NULL
+                    ORDER BY added_at ASC
+                    LIMIT 1
+                    "#
+                ),
+                params![content_hash],
+                picture_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await?
+    }
+
+    /// Whether a picture, live or in the trash, is stored under `filename`.
+    pub async fn is_filename_used(&self, filename: &str) -> Result<bool> {
+        let pool = self.pool.clone();
+        let filename = filename.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.prepare("SELECT 1 FROM pictures WHERE filename = ?1")?
+                .exists([&filename])
+                .map_err(Into::into)
+        })
+        .await?
+    }
+
+    /// Live picture stored under `filename`, if any.
+    pub async fn find_picture_by_filename(&self, filename: &str) -> Result<Option<Picture>> {
+        let pool = self.pool.clone();
+        let filename = filename.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.query_row(
+                &format!(
+                    r#"
+                    SELECT {PICTURE_COLUMNS}
+                    FROM pictures
+                    WHERE filename = ?1 AND deleted_at IS NULL
+                    "#
+                ),
+                params![filename],
+                picture_from_row,
             )
             .optional()
             .map_err(Into::into)
         })
         .await?
     }
+
+    /// Live pictures that are not favorites and not stored as `pinned`,
+    /// oldest first, i.e. in the order they are given up for space.
+    pub async fn list_evictable_pictures(&self, pinned: Option<String>) -> Result<Vec<Picture>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(&format!(
+                r#"
+                SELECT {PICTURE_COLUMNS}
+                FROM pictures
+                WHERE deleted_at IS NULL AND favorite = 0 AND filename IS NOT ?1
+                ORDER BY added_at ASC, id ASC
+                "#
+            ))?;
+            let pictures = stmt
+                .query_map(params![pinned], picture_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(pictures)
+        })
+        .await?
+    }
+
+    /// Pictures stored before content hashes were recorded.
+    pub async fn list_unhashed_pictures(&self) -> Result<Vec<Picture>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {PICTURE_COLUMNS} FROM pictures WHERE content_hash IS NULL AND deleted_at IS NULL"
+            ))?;
+            let pics = stmt
+                .query_map([], picture_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(pics)
+        })
+        .await?
+    }
+
+    /// Pictures whose metadata has never been extracted.
+    pub async fn list_pictures_without_metadata(&self) -> Result<Vec<Picture>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {PICTURE_COLUMNS} FROM pictures WHERE width IS NULL AND deleted_at IS NULL"
+            ))?;
+            let pics = stmt
+                .query_map([], picture_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(pics)
+        })
+        .await?
+    }
+
+    pub async fn set_picture_metadata(&self, id: &str, metadata: PictureMetadata) -> Result<()> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let m = metadata;
+            conn.execute(
+                r#"
+                UPDATE pictures
+                SET taken_at = ?2, camera_make = ?3, camera_model = ?4, width = ?5, height = ?6,
+                    orientation = ?7, gps_latitude = ?8, gps_longitude = ?9
+                WHERE id = ?1
+                "#,
+                params![
+                    id,
+                    m.taken_at,
+                    m.camera_make,
+                    m.camera_model,
+                    m.width,
+                    m.height,
+                    m.orientation,
+                    m.gps_latitude,
+                    m.gps_longitude
+                ],
+            )?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Apply every change in `edit` or none. Returns whether the picture exists
+    /// outside the trash.
+    pub async fn update_picture(&self, id: &str, edit: PictureEdit) -> Result<bool> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            let tx = conn.transaction()?;
+            if !is_live(&tx, &id)? {
+                return Ok(false);
+            }
+            if let Some(title) = edit.title {
+                tx.execute(
+                    "UPDATE pictures SET title = ?2 WHERE id = ?1",
+                    params![id, title],
+                )?;
+            }
+            if let Some(caption) = edit.caption {
+                tx.execute(
+                    "UPDATE pictures SET caption = ?2 WHERE id = ?1",
+                    params![id, caption],
+                )?;
+            }
+            if let Some(favorite) = edit.favorite {
+                tx.execute(
+                    "UPDATE pictures SET favorite = ?2 WHERE id = ?1",
+                    params![id, favorite],
+                )?;
+            }
+            if let Some(weight) = edit.weight {
+                tx.execute(
+                    "UPDATE pictures SET weight = ?2 WHERE id = ?1",
+                    params![id, weight],
+                )?;
+            }
+            if let Some(rules) = edit.schedule {
+                tx.execute(
+                    "UPDATE pictures SET schedule = ?2 WHERE id = ?1",
+                    params![id, schedule_to_sql(&rules)?],
+                )?;
+            }
+            if let Some(crop) = edit.crop {
+                tx.execute(
+                    r#"
+                    UPDATE pictures
+                    SET crop_x = ?2, crop_y = ?3, crop_width = ?4, crop_height = ?5
+                    WHERE id = ?1
+                    "#,
+                    params![
+                        id,
+                        crop.map(|c| c.x),
+                        crop.map(|c| c.y),
+                        crop.map(|c| c.width),
+                        crop.map(|c| c.height),
+                    ],
+                )?;
+            }
+            if let Some(focal_point) = edit.focal_point {
+                tx.execute(
+                    "UPDATE pictures SET focal_x = ?2, focal_y = ?3 WHERE id = ?1",
+                    params![id, focal_point.map(|f| f.x), focal_point.map(|f| f.y)],
+                )?;
+            }
+            tx.commit()?;
+            Ok(true)
+        })
+        .await?
+    }
+
+    /// `(filename, favorite, weight)` of every picture that is a favorite or
+    /// has a weight other than 1.
+    pub async fn list_picture_weightings(&self) -> Result<Vec<(String, bool, u32)>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT filename, favorite, weight
+                FROM pictures
+                WHERE (favorite OR weight != 1) AND deleted_at IS NULL
+                "#,
+            )?;
+            let rows = stmt
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })
+        .await?
+    }
+
+    /// `(filename, rules)` of every picture with a schedule.
+    pub async fn list_picture_schedules(&self) -> Result<Vec<(String, Vec<ScheduleRule>)>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT filename, schedule
+                FROM pictures
+                WHERE schedule IS NOT NULL AND deleted_at IS NULL
+                "#,
+            )?;
+            let rows = stmt
+                .query_map([], |r| Ok((r.get(0)?, schedule_from_sql(r, 1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })
+        .await?
+    }
+
+    /// `(filename, crop, focal point)` of every picture that has either.
+    pub async fn list_picture_framings(
+        &self,
+    ) -> Result<Vec<(String, Option<Crop>, Option<FocalPoint>)>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT filename, crop_x, crop_y, crop_width, crop_height, focal_x, focal_y
+                FROM pictures
+                WHERE (crop_x IS NOT NULL OR focal_x IS NOT NULL) AND deleted_at IS NULL
+                "#,
+            )?;
+            let rows = stmt
+                .query_map([], |r| {
+                    Ok((r.get(0)?, crop_from_sql(r, 1)?, focal_point_from_sql(r, 5)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })
+        .await?
+    }
+
+    /// Set the rotation together with the crop and focal point, which are
+    /// measured on the turned picture. Returns whether the picture exists
+    /// outside the trash.
+    pub async fn set_picture_rotation(
+        &self,
+        id: &str,
+        rotation: Rotation,
+        crop: Option<Crop>,
+        focal_point: Option<FocalPoint>,
+    ) -> Result<bool> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let n = conn.execute(
+                r#"
+                UPDATE pictures
+                SET rotation = ?2, flipped = ?3,
+                    crop_x = ?4, crop_y = ?5, crop_width = ?6, crop_height = ?7,
+                    focal_x = ?8, focal_y = ?9
+                WHERE id = ?1 AND deleted_at IS NULL
+                "#,
+                params![
+                    id,
+                    rotation.degrees,
+                    rotation.flip,
+                    crop.map(|c| c.x),
+                    crop.map(|c| c.y),
+                    crop.map(|c| c.width),
+                    crop.map(|c| c.height),
+                    focal_point.map(|f| f.x),
+                    focal_point.map(|f| f.y),
+                ],
+            )?;
+            Ok(n > 0)
+        })
+        .await?
+    }
+
+    /// `(filename, rotation)` of every picture with a rotation override.
+    pub async fn list_picture_rotations(&self) -> Result<Vec<(String, Rotation)>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT filename, rotation, flipped
+                FROM pictures
+                WHERE (rotation != 0 OR flipped) AND deleted_at IS NULL
+                "#,
+            )?;
+            let rows = stmt
+                .query_map([], |r| {
+                    Ok((
+                        r.get(0)?,
+                        Rotation {
+                            degrees: r.get(1)?,
+                            flip: r.get(2)?,
+                        },
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })
+        .await?
+    }
+
+    /// `(filename, title, caption)` of every picture that has either.
+    pub async fn list_picture_texts(
+        &self,
+    ) -> Result<Vec<(String, Option<String>, Option<String>)>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT filename, title, caption
+                FROM pictures
+                WHERE (title IS NOT NULL OR caption IS NOT NULL) AND deleted_at IS NULL
+                "#,
+            )?;
+            let rows = stmt
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })
+        .await?
+    }
+
+    /// Record the hash of an older picture; if a live picture already has
+    /// it, this one is marked as a copy of it.
+    pub async fn set_content_hash(&self, id: &str, content_hash: &str) -> Result<()> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        let content_hash = content_hash.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.execute(
+                r#"
+                UPDATE pictures
+                SET content_hash = ?2,
+                    duplicate_of = (
+                        SELECT p.id FROM pictures p
+                        WHERE p.content_hash = ?2 AND p.deleted_at IS NULL
+                          AND p.duplicate_of IS NULL AND p.id != ?1
+                    )
+                WHERE id = ?1
+                "#,
+                params![id, content_hash],
+            )?;
+            Ok(())
+        })
+        .await?
+    }
+}
+
+impl Repository {
+    pub async fn create_upload_session(
+        &self,
+        length: u64,
+        content_type: Option<String>,
+    ) -> Result<UploadSession> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let now = chrono::Utc::now().timestamp_millis();
+            let session = UploadSession {
+                id: uuid::Uuid::new_v4().to_string(),
+                length,
+                offset: 0,
+                content_type,
+                created_at: now,
+                updated_at: now,
+            };
+            conn.execute(
+                r#"
+                INSERT INTO upload_sessions (id, length, offset, content_type, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                "#,
+                params![
+                    session.id,
+                    session.length,
+                    session.offset,
+                    session.content_type,
+                    session.created_at,
+                    session.updated_at
+                ],
+            )?;
+            Ok(session)
+        })
+        .await?
+    }
+
+    pub async fn get_upload_session(&self, id: &str) -> Result<Option<UploadSession>> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.query_row(
+                r#"
+                SELECT id, length, offset, content_type, created_at, updated_at
+                FROM upload_sessions
+                WHERE id = ?1
+                "#,
+                params![id],
+                |row| {
+                    Ok(UploadSession {
+                        id: row.get(0)?,
+                        length: row.get(1)?,
+                        offset: row.get(2)?,
+                        content_type: row.get(3)?,
+                        created_at: row.get(4)?,
+                        updated_at: row.get(5)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await?
+    }
+
+    /// Record how many bytes of the session have been written and refresh its expiry.
+    pub async fn set_upload_offset(&self, id: &str, offset: u64) -> Result<()> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.execute(
+                "UPDATE upload_sessions SET offset = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id, offset, chrono::Utc::now().timestamp_millis()],
+            )?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Returns whether a session was deleted.
+    pub async fn delete_upload_session(&self, id: &str) -> Result<bool> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let n = conn.execute("DELETE FROM upload_sessions WHERE id = ?1", [&id])?;
+            Ok(n > 0)
+        })
+        .await?
+    }
+
+    /// List sessions not touched since `cutoff` (unix millis).
+    pub async fn list_stale_upload_sessions(&self, cutoff: i64) -> Result<Vec<String>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare("SELECT id FROM upload_sessions WHERE updated_at < ?1")?;
+            let ids = stmt
+                .query_map([cutoff], |r| r.get(0))?
+                .collect::<Result<Vec<String>, _>>()?;
+            Ok(ids)
+        })
+        .await?
+    }
+
+    /// Delete a session unless it was updated since `cutoff`.
+    pub async fn delete_stale_upload_session(&self, id: &str, cutoff: i64) -> Result<bool> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let n = conn.execute(
+                "DELETE FROM upload_sessions WHERE id = ?1 AND updated_at < ?2",
+                params![id, cutoff],
+            )?;
+            Ok(n > 0)
+        })
+        .await?
+    }
+
+    pub async fn list_upload_session_ids(&self) -> Result<Vec<String>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare("SELECT id FROM upload_sessions")?;
+            let ids = stmt
+                .query_map([], |r| r.get(0))?
+                .collect::<Result<Vec<String>, _>>()?;
+            Ok(ids)
+        })
+        .await?
+    }
+}
+
+impl Repository {
+    pub async fn list_albums(&self) -> Result<Vec<Album>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt =
+                conn.prepare(&format!("{ALBUM_SELECT} GROUP BY a.id ORDER BY a.name"))?;
+            let albums = stmt
+                .query_map([], album_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(albums)
+        })
+        .await?
+    }
+
+    pub async fn get_album(&self, id: &str) -> Result<Option<Album>> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.query_row(
+                &format!("{ALBUM_SELECT} WHERE a.id = ?1 GROUP BY a.id"),
+                params![id],
+                album_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+        .await?
+    }
+
+    /// Fails with a unique violation if the name is taken.
+    pub async fn create_album(&self, name: &str) -> Result<Album> {
+        let pool = self.pool.clone();
+        let name = name.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let album = Album {
+                id: uuid::Uuid::new_v4().to_string(),
+                name,
+                created_at: chrono::Utc::now().timestamp_millis(),
+                picture_count: 0,
+            };
+            conn.execute(
+                "INSERT INTO albums (id, name, created_at) VALUES (?1, ?2, ?3)",
+                params![album.id, album.name, album.created_at],
+            )?;
+            Ok(album)
+        })
+        .await?
+    }
+
+    /// Returns whether the album exists. Fails with a unique violation if the
+    /// name is taken.
+    pub async fn rename_album(&self, id: &str, name: &str) -> Result<bool> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        let name = name.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let n = conn.execute(
+                "UPDATE albums SET name = ?2 WHERE id = ?1",
+                params![id, name],
+            )?;
+            Ok(n > 0)
+        })
+        .await?
+    }
+
+    /// Deletes the album and its memberships; the pictures stay.
+    pub async fn delete_album(&self, id: &str) -> Result<bool> {
+        let pool = self.pool.clone();
+        let id = id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let n = conn.execute("DELETE FROM albums WHERE id = ?1", [&id])?;
+            Ok(n > 0)
+        })
+        .await?
+    }
+
+    /// Both sides must exist. Adding a picture twice is a no-op.
+    pub async fn add_album_picture(&self, album_id: &str, picture_id: &str) -> Result<()> {
+        let pool = self.pool.clone();
+        let album_id = album_id.to_owned();
+        let picture_id = picture_id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.execute(
+                r#"
+                INSERT OR IGNORE INTO album_pictures (album_id, picture_id, added_at)
+                VALUES (?1, ?2, ?3)
+                "#,
+                params![album_id, picture_id, chrono::Utc::now().timestamp_millis()],
+            )?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Returns whether the picture was in the album.
+    pub async fn remove_album_picture(&self, album_id: &str, picture_id: &str) -> Result<bool> {
+        let pool = self.pool.clone();
+        let album_id = album_id.to_owned();
+        let picture_id = picture_id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let n = conn.execute(
+                "DELETE FROM album_pictures WHERE album_id = ?1 AND picture_id = ?2",
+                params![album_id, picture_id],
+            )?;
+            Ok(n > 0)
+        })
+        .await?
+    }
+
+    /// Pictures of an album, most recently added to it first.
+    pub async fn list_album_pictures(&self, album_id: &str) -> Result<Vec<Picture>> {
+        let pool = self.pool.clone();
+        let album_id = album_id.to_owned();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(&format!(
+                r#"
+                SELECT {PICTURE_COLUMNS}
+                FROM pictures
+                JOIN album_pictures ap ON ap.picture_id = pictures.id
+                WHERE ap.album_id = ?1 AND pictures.deleted_at IS NULL
+                ORDER BY ap.added_at DESC
+                "#
+            ))?;
+            let pics = stmt
+                .query_map([&album_id], picture_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(pics)
+        })
+        .await?
+    }
+
+    /// `(album id, picture filename)` for every membership.
+    pub async fn list_album_memberships(&self) -> Result<Vec<(String, String)>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT ap.album_id, p.filename
+                FROM album_pictures ap
+                JOIN pictures p ON p.id = ap.picture_id
+                WHERE p.deleted_at IS NULL
+                ORDER BY ap.album_id, p.filename
+                "#,
+            )?;
+            let rows = stmt
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })
+        .await?
+    }
+}
+
+impl Repository {
+    /// Tags in use, with how many pictures carry each.
+    pub async fn list_tags(&self) -> Result<Vec<Tag>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT t.name, COUNT(*)
+                FROM tags t
+                JOIN picture_tags pt ON pt.tag_id = t.id
+                JOIN pictures p ON p.id = pt.picture_id AND p.deleted_at IS NULL
+                GROUP BY t.id
+                ORDER BY t.name
+                "#,
+            )?;
+            let tags = stmt
+                .query_map([], |r| {
+                    Ok(Tag {
+                        name: r.get(0)?,
+                        picture_count: r.get(1)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(tags)
+        })
+        .await?
+    }
+
+    /// `tag` must be normalized. Tagging a picture twice is a no-op.
+    pub async fn add_picture_tag(&self, picture_id: &str, tag: &str) -> Result<()> {
+        let pool = self.pool.clone();
+        let picture_id = picture_id.to_owned();
+        let tag = tag.to_owned();
+        task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            let tx = conn.transaction()?;
+            tx.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [&tag])?;
+            tx.execute(
+                r#"
+                INSERT OR IGNORE INTO picture_tags (picture_id, tag_id)
+                SELECT ?1, id FROM tags WHERE name = ?2
+                "#,
+                params![picture_id, tag],
+            )?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Returns whether the picture had the tag. Tags no picture carries any
+    /// more are dropped.
+    pub async fn remove_picture_tag(&self, picture_id: &str, tag: &str) -> Result<bool> {
+        let pool = self.pool.clone();
+        let picture_id = picture_id.to_owned();
+        let tag = tag.to_owned();
+        task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            let tx = conn.transaction()?;
+            let n = tx.execute(
+                r#"
+                DELETE FROM picture_tags
+                WHERE picture_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)
+                "#,
+                params![picture_id, tag],
+            )?;
+            prune_tags(&tx)?;
+            tx.commit()?;
+            Ok(n > 0)
+        })
+        .await?
+    }
+
+    /// `(picture filename, tag)` for every tagging.
+    pub async fn list_picture_tags(&self) -> Result<Vec<(String, String)>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT p.filename, t.name
+                FROM picture_tags pt
+                JOIN pictures p ON p.id = pt.picture_id
+                JOIN tags t ON t.id = pt.tag_id
+                WHERE p.deleted_at IS NULL
+                ORDER BY p.filename, t.name
+                "#,
+            )?;
+            let rows = stmt
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        })
+        .await?
+    }
+}
+
+/// Bulk variants of the single-picture updates. Each runs in one transaction
+/// and reports an outcome per id, in order.
+impl Repository {
+    /// Trash the pictures; yields the filename of each one trashed.
+    pub async fn trash_pictures(&self, ids: Vec<String>) -> Result<Vec<Option<String>>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            let tx = conn.transaction()?;
+            let now = chrono::Utc::now().timestamp_millis();
+            let filenames = for_each_id(&tx, &ids, |tx, id| {
+                tx.query_row(
+                    r#"
+                    UPDATE pictures SET deleted_at = ?2
+                    WHERE id = ?1 AND deleted_at IS NULL
+                    RETURNING filename
+                    "#,
+                    params![id, now],
+                    |r| r.get(0),
+                )
+                .optional()
+            })?;
+            tx.commit()?;
+            Ok(filenames)
+        })
+        .await?
+    }
+
+    /// The album must exist.
+    pub async fn add_pictures_to_album(
+        &self,
+        album_id: &str,
+        ids: Vec<String>,
+    ) -> Result<Vec<BulkOutcome>> {
+        let pool = self.pool.clone();
+        let album_id = album_id.to_owned();
+        task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            let tx = conn.transaction()?;
+            let now = chrono::Utc::now().timestamp_millis();
+            let outcomes = for_each_id(&tx, &ids, |tx, id| {
+                if !is_live(tx, id)? {
+                    return Ok(BulkOutcome::NotFound);
+                }
+                let n = tx.execute(
+                    r#"
+                    INSERT OR IGNORE INTO album_pictures (album_id, picture_id, added_at)
+                    VALUES (?1, ?2, ?3)
+                    "#,
+                    params![album_id, id, now],
+                )?;
+                Ok(changed(n))
+            })?;
+            tx.commit()?;
+            Ok(outcomes)
+        })
+        .await?
+    }
+
+    /// `tag` must be normalized.
+    pub async fn tag_pictures(&self, ids: Vec<String>, tag: &str) -> Result<Vec<BulkOutcome>> {
+        let pool = self.pool.clone();
+        let tag = tag.to_owned();
+        task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            let tx = conn.transaction()?;
+            tx.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [&tag])?;
+            let tag_id: i64 =
+                tx.query_row("SELECT id FROM tags WHERE name = ?1", [&tag], |r| r.get(0))?;
+            let outcomes = for_each_id(&tx, &ids, |tx, id| {
+                if !is_live(tx, id)? {
+                    return Ok(BulkOutcome::NotFound);
+                }
+                let n = tx.execute(
+                    "INSERT OR IGNORE INTO picture_tags (picture_id, tag_id) VALUES (?1, ?2)",
+                    params![id, tag_id],
+                )?;
+                Ok(changed(n))
+            })?;
+            // the tag may have been created for nothing
+            prune_tags(&tx)?;
+            tx.commit()?;
+            Ok(outcomes)
+        })
+        .await?
+    }
+
+    pub async fn untag_pictures(&self, ids: Vec<String>, tag: &str) -> Result<Vec<BulkOutcome>> {
+        let pool = self.pool.clone();
+        let tag = tag.to_owned();
+        task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            let tx = conn.transaction()?;
+            let outcomes = for_each_id(&tx, &ids, |tx, id| {
+                if !is_live(tx, id)? {
+                    return Ok(BulkOutcome::NotFound);
+                }
+                let n = tx.execute(
+                    r#"
+                    DELETE FROM picture_tags
+                    WHERE picture_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)
+                    "#,
+                    params![id, tag],
+                )?;
+                Ok(changed(n))
+            })?;
+            prune_tags(&tx)?;
+            tx.commit()?;
+            Ok(outcomes)
+        })
+        .await?
+    }
+
+    pub async fn set_pictures_caption(
+        &self,
+        ids: Vec<String>,
+        caption: Option<String>,
+    ) -> Result<Vec<BulkOutcome>> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            let tx = conn.transaction()?;
+            let outcomes = for_each_id(&tx, &ids, |tx, id| {
+                let n = tx.execute(
+                    r#"
+                    UPDATE pictures SET caption = ?2
+                    WHERE id = ?1 AND deleted_at IS NULL AND caption IS NOT ?2
+                    "#,
+                    params![id, caption],
+                )?;
+                if n > 0 {
+                    Ok(BulkOutcome::Done)
+                } else if is_live(tx, id)? {
+                    Ok(BulkOutcome::Unchanged)
+                } else {
+                    Ok(BulkOutcome::NotFound)
+                }
+            })?;
+            tx.commit()?;
+            Ok(outcomes)
+        })
+        .await?
+    }
+
+    /// Write a consistent copy of the database to `dest` with SQLite's online
+    /// backup API, while other connections keep working.
+    pub async fn backup_database(&self, dest: PathBuf) -> Result<()> {
+        let pool = self.pool.clone();
+        task::spawn_blocking(move || {
+            let conn = pool.get()?;
+            conn.backup(DatabaseName::Main, &dest, None)?;
+            Ok(())
+        })
+        .await?
+    }
 }
 
 impl Repository {
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// Longest tag we accept, in characters.
+const MAX_TAG_LEN: usize = 64;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub picture_count: u64,
+}
+
+/// Canonical form of a user-supplied tag: trimmed and lower-cased. `None` if
+/// it is empty, too long or contains a comma or control character (tags are
+/// passed comma-separated in queries).
+pub fn normalize_tag(raw: &str) -> Option<String> {
+    let tag = raw.trim().to_lowercase();
+    let valid = !tag.is_empty()
+        && tag.chars().count() <= MAX_TAG_LEN
+        && !tag.chars().any(|c| c == ',' || c.is_control());
+    valid.then_some(tag)
+}
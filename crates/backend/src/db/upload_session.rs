@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// A resumable upload in progress; the bytes live in a temp file until finalized.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UploadSession {
+    pub id: String,
+    /// Total size announced by the client.
+    pub length: u64,
+    /// Bytes received so far.
+    pub offset: u64,
+    pub content_type: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
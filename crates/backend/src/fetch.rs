@@ -0,0 +1,525 @@
+//! Downloads pictures for `POST /api/pictures/import`.
+
+use std::{
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    task::{self, Poll},
+    time::Duration,
+};
+
+use axum::{
+    body::Bytes,
+    http::{Request, StatusCode, Uri, header},
+};
+use http_body_util::{BodyExt, Empty};
+use hyper_rustls::HttpsConnector;
+use hyper_util::{
+    client::legacy::{
+        Client,
+        connect::{HttpConnector, dns::Name},
+    },
+    rt::TokioExecutor,
+};
+use once_cell::sync::OnceCell;
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tower::Service;
+
+use crate::{CONFIG, common::ApiError};
+
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error("invalid URL: {0}")]
+    InvalidUrl(String),
+    #[error("{0} is not a public address")]
+    NotPublic(String),
+    #[error("too many redirects")]
+    TooManyRedirects,
+    #[error("server answered {0}")]
+    Status(StatusCode),
+    #[error("download is larger than {0} bytes")]
+    TooLarge(u64),
+    #[error("download took longer than {0:?}")]
+    Timeout(Duration),
+    #[error("request failed: {0}")]
+    Request(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<FetchError> for ApiError {
+    fn from(err: FetchError) -> Self {
+        match err {
+            FetchError::InvalidUrl(_) | FetchError::NotPublic(_) => {
+                ApiError::BadRequest(err.to_string())
+            }
+            FetchError::TooLarge(_) => ApiError::PayloadTooLarge(err.to_string()),
+            FetchError::Io(e) => ApiError::Internal(e.into()),
+            _ => ApiError::BadGateway(err.to_string()),
+        }
+    }
+}
+
+type HttpsClient = Client<HttpsConnector<HttpConnector<Resolver>>, Empty<Bytes>>;
+
+/// HTTP(S) client with a size cap and a timeout for the whole download.
+/// Plain `http` is allowed, so a local server can stand in for a real one.
+/// Only public addresses are reached unless `allow_private` is set, so an API
+/// key cannot be used to probe the host or its network.
+pub struct Fetcher {
+    /// Built on first use, so a host without CA certificates only loses
+    /// imports.
+    client: OnceCell<HttpsClient>,
+    max_bytes: u64,
+    timeout: Duration,
+    allow_private: bool,
+}
+
+impl Fetcher {
+    pub fn new(max_bytes: u64, timeout: Duration, allow_private: bool) -> Self {
+        Fetcher {
+            client: OnceCell::new(),
+            max_bytes,
+            timeout,
+            allow_private,
+        }
+    }
+
+    pub fn from_config() -> Self {
+        Self::new(
+            CONFIG.backend_import_max_bytes,
+            Duration::from_secs(CONFIG.backend_import_timeout_secs),
+            CONFIG.backend_import_allow_private,
+        )
+    }
+
+    fn client(&self) -> Result<&HttpsClient, FetchError> {
+        self.client.get_or_try_init(|| {
+            let mut http = HttpConnector::new_with_resolver(Resolver {
+                allow_private: self.allow_private,
+            });
+            http.enforce_http(false);
+            let https = hyper_rustls::HttpsConnectorBuilder::new()
+                .with_native_roots()
+                .inspect_err(|e| tracing::error!("cannot load CA certificates: {e}"))?
+                .https_or_http()
+                .enable_http1()
+                .wrap_connector(http);
+            Ok(Client::builder(TokioExecutor::new()).build(https))
+        })
+    }
+
+    /// Download `url` into `dest`, following redirects.
+    pub async fn fetch(&self, url: &str, dest: &mut tokio::fs::File) -> Result<(), FetchError> {
+        let uri = parse_url(url)?;
+        tokio::time::timeout(self.timeout, self.download(uri, dest))
+            .await
+            .map_err(|_| FetchError::Timeout(self.timeout))?
+    }
+
+    async fn download(&self, mut uri: Uri, dest: &mut tokio::fs::File) -> Result<(), FetchError> {
+        let mut redirects = 0;
+        let mut response = loop {
+            // the resolver only sees names, not addresses written out
+            if let Some(ip) = uri.host().and_then(literal_ip)
+                && !self.allow_private
+                && !is_public(ip)
+            {
+                return Err(FetchError::NotPublic(ip.to_string()));
+            }
+            let req = Request::get(&uri)
+                .header(header::USER_AGENT, "picture_frame")
+                .body(Empty::new())
+                .map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+            let response = self.client()?.request(req).await.map_err(request_error)?;
+            if !response.status().is_redirection() {
+                break response;
+            }
+            redirects += 1;
+            if redirects > MAX_REDIRECTS {
+                return Err(FetchError::TooManyRedirects);
+            }
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .ok_or(FetchError::Status(response.status()))?;
+            uri = resolve(&uri, location)?;
+            tracing::debug!("following redirect to {uri}");
+        };
+
+        if !response.status().is_success() {
+            return Err(FetchError::Status(response.status()));
+        }
+        let declared_len = response
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|l| l.to_str().ok()?.parse::<u64>().ok());
+        if declared_len.is_some_and(|len| len > self.max_bytes) {
+            return Err(FetchError::TooLarge(self.max_bytes));
+        }
+
+        let mut written = 0u64;
+        while let Some(frame) = response.body_mut().frame().await {
+            let frame = frame.map_err(|e| FetchError::Request(e.to_string()))?;
+            let Ok(chunk) = frame.into_data() else {
+                continue;
+            };
+            written += chunk.len() as u64;
+            if written > self.max_bytes {
+                return Err(FetchError::TooLarge(self.max_bytes));
+            }
+            dest.write_all(&chunk).await?;
+        }
+        dest.flush().await?;
+        Ok(())
+    }
+}
+
+/// Name resolution that drops every address that is not public, unless
+/// `allow_private` is set. Checking here rather than before the request means
+/// a name cannot resolve to something else by the time it is connected to.
+#[derive(Clone)]
+struct Resolver {
+    allow_private: bool,
+}
+
+impl Service<Name> for Resolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut task::Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let allow_private = self.allow_private;
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| allow_private || is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    FetchError::NotPublic(name.as_str().to_owned()),
+                ));
+            }
+            Ok(addrs.into_iter())
+        })
+    }
+}
+
+/// The host of a URL if it is an address rather than a name.
+fn literal_ip(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Whether `ip` can be reached from anywhere on the internet, as opposed to
+/// the host itself, a private network or a reserved range.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || a >= 240
+                // shared address space (carrier-grade NAT)
+                || (a == 100 && b & 0xc0 == 64)
+                // benchmarking
+                || (a == 198 && b & 0xfe == 18)
+                // IETF protocol assignments
+                || (a == 192 && b == 0 && c == 0))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // unique local
+                || first & 0xfe00 == 0xfc00
+                // link-local
+                || first & 0xffc0 == 0xfe80
+                // documentation
+                || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+        }
+    }
+}
+
+/// A failed request, telling a refused address apart from other failures.
+fn request_error(err: hyper_util::client::legacy::Error) -> FetchError {
+    let mut source = std::error::Error::source(&err);
+    while let Some(e) = source {
+        // an io::Error hides what it wraps from the source chain
+        if let Some(FetchError::NotPublic(host)) = e
+            .downcast_ref::<io::Error>()
+            .and_then(|e| e.get_ref()?.downcast_ref())
+        {
+            return FetchError::NotPublic(host.clone());
+        }
+        source = e.source();
+    }
+    FetchError::Request(err.to_string())
+}
+
+fn parse_url(url: &str) -> Result<Uri, FetchError> {
+    let uri: Uri = url
+        .parse()
+        .map_err(|e: axum::http::uri::InvalidUri| FetchError::InvalidUrl(e.to_string()))?;
+    match uri.scheme_str() {
+        Some("http" | "https") if uri.authority().is_some() => Ok(uri),
+        _ => Err(FetchError::InvalidUrl(
+            "only absolute http and https URLs can be imported".into(),
+        )),
+    }
+}
+
+/// Target of a redirect from `base`; `location` may be relative.
+fn resolve(base: &Uri, location: &str) -> Result<Uri, FetchError> {
+    if let Ok(uri) = location.parse::<Uri>()
+        && uri.scheme().is_some()
+    {
+        return parse_url(location);
+    }
+    if location.starts_with("//") {
+        return parse_url(&format!(
+            "{}:{location}",
+            base.scheme_str().unwrap_or("https")
+        ));
+    }
+    let path = if location.starts_with('/') {
+        location.to_owned()
+    } else {
+        let dir = base.path().rsplit_once('/').map_or("", |(dir, _)| dir);
+        format!("{dir}/{location}")
+    };
+    let mut parts = base.clone().into_parts();
+    parts.path_and_query = Some(
+        path.parse()
+            .map_err(|e: axum::http::uri::InvalidUri| FetchError::InvalidUrl(e.to_string()))?,
+    );
+    Uri::from_parts(parts).map_err(|e| FetchError::InvalidUrl(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    const BODY: &[u8] = b"not really a picture";
+
+    /// Serve canned responses by path on a local port; returns the base URL.
+    async fn stand_in() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        let n = socket.read(&mut buf).await.unwrap();
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                    }
+                    let request = String::from_utf8_lossy(&request);
+                    let path = request.split(' ').nth(1).unwrap_or_default();
+                    let head = |status: &str, headers: &str| {
+                        format!("HTTP/1.1 {status}\r\nConnection: close\r\n{headers}\r\n")
+                    };
+                    let response = match path {
+                        "/pictures/ok" => {
+                            let mut r =
+                                head("200 OK", &format!("Content-Length: {}\r\n", BODY.len()))
+                                    .into_bytes();
+                            r.extend_from_slice(BODY);
+                            r
+                        }
+                        "/pictures/chunked" => {
+                            let mut r =
+                                head("200 OK", "Transfer-Encoding: chunked\r\n").into_bytes();
+                            for _ in 0..4 {
+                                r.extend_from_slice(format!("{:x}\r\n", BODY.len()).as_bytes());
+                                r.extend_from_slice(BODY);
+                                r.extend_from_slice(b"\r\n");
+                            }
+                            r.extend_from_slice(b"0\r\n\r\n");
+                            r
+                        }
+                        "/pictures/huge" => {
+                            head("200 OK", "Content-Length: 1000000\r\n").into_bytes()
+                        }
+                        "/pictures/redirect" => {
+                            head("302 Found", "Location: ok\r\nContent-Length: 0\r\n").into_bytes()
+                        }
+                        "/pictures/loop" => head(
+                            "302 Found",
+                            "Location: /pictures/loop\r\nContent-Length: 0\r\n",
+                        )
+                        .into_bytes(),
+                        "/pictures/slow" => {
+                            tokio::time::sleep(Duration::from_secs(30)).await;
+                            return;
+                        }
+                        _ => head("404 Not Found", "Content-Length: 0\r\n").into_bytes(),
+                    };
+                    let _ = socket.write_all(&response).await;
+                });
+            }
+        });
+        format!("http://{addr}/pictures")
+    }
+
+    async fn fetch(fetcher: &Fetcher, url: &str) -> Result<Vec<u8>, FetchError> {
+        let path = std::env::temp_dir().join(format!("fetch-test-{}", uuid::Uuid::new_v4()));
+        let mut dest = tokio::fs::File::create(&path).await.unwrap();
+        let fetched = fetcher.fetch(url, &mut dest).await;
+        let bytes = tokio::fs::read(&path).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        fetched.map(|()| bytes)
+    }
+
+    fn fetcher(max_bytes: u64) -> Fetcher {
+        // the stand-in is on loopback
+        Fetcher::new(max_bytes, Duration::from_secs(5), true)
+    }
+
+    #[tokio::test]
+    async fn downloads_a_file() {
+        let base = stand_in().await;
+        let bytes = fetch(&fetcher(1024), &format!("{base}/ok")).await.unwrap();
+        assert_eq!(bytes, BODY);
+    }
+
+    #[tokio::test]
+    async fn follows_a_relative_redirect() {
+        let base = stand_in().await;
+        let bytes = fetch(&fetcher(1024), &format!("{base}/redirect"))
+            .await
+            .unwrap();
+        assert_eq!(bytes, BODY);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_redirect_loops() {
+        let base = stand_in().await;
+        let result = fetch(&fetcher(1024), &format!("{base}/loop")).await;
+        assert!(
+            matches!(result, Err(FetchError::TooManyRedirects)),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_a_declared_length_over_the_cap() {
+        let base = stand_in().await;
+        let result = fetch(&fetcher(1024), &format!("{base}/huge")).await;
+        assert!(
+            matches!(result, Err(FetchError::TooLarge(1024))),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_an_undeclared_length_at_the_cap() {
+        let base = stand_in().await;
+        let cap = BODY.len() as u64 * 2;
+        let result = fetch(&fetcher(cap), &format!("{base}/chunked")).await;
+        assert!(
+            matches!(result, Err(FetchError::TooLarge(c)) if c == cap),
+            "{result:?}"
+        );
+        let bytes = fetch(&fetcher(1024), &format!("{base}/chunked"))
+            .await
+            .unwrap();
+        assert_eq!(bytes, BODY.repeat(4));
+    }
+
+    #[tokio::test]
+    async fn refuses_private_addresses() {
+        let base = stand_in().await;
+        let strict = Fetcher::new(1024, Duration::from_secs(5), false);
+        for url in [
+            format!("{base}/ok"),
+            base.replace("127.0.0.1", "localhost") + "/ok",
+        ] {
+            let result = fetch(&strict, &url).await;
+            assert!(
+                matches!(result, Err(FetchError::NotPublic(_))),
+                "{url}: {result:?}"
+            );
+        }
+        let by_name = base.replace("127.0.0.1", "localhost") + "/ok";
+        assert_eq!(fetch(&fetcher(1024), &by_name).await.unwrap(), BODY);
+
+        for private in [
+            "10.1.2.3",
+            "169.254.169.254",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+            "::ffff:192.168.0.1",
+        ] {
+            assert!(!is_public(private.parse().unwrap()), "{private}");
+        }
+        for public in ["93.184.216.34", "2606:2800:220:1::1"] {
+            assert!(is_public(public.parse().unwrap()), "{public}");
+        }
+    }
+
+    #[tokio::test]
+    async fn times_out() {
+        let base = stand_in().await;
+        let fetcher = Fetcher::new(1024, Duration::from_millis(200), true);
+        let result = fetch(&fetcher, &format!("{base}/slow")).await;
+        assert!(matches!(result, Err(FetchError::Timeout(_))), "{result:?}");
+    }
+
+    #[tokio::test]
+    async fn reports_error_statuses() {
+        let base = stand_in().await;
+        let result = fetch(&fetcher(1024), &format!("{base}/missing")).await;
+        assert!(
+            matches!(result, Err(FetchError::Status(StatusCode::NOT_FOUND))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn resolves_redirect_targets() {
+        let base: Uri = "https://example.com/a/b.jpg?x=1".parse().unwrap();
+        for (location, expected) in [
+            ("c.jpg", "https://example.com/a/c.jpg"),
+            ("/c.jpg", "https://example.com/c.jpg"),
+            ("//cdn.example.com/c.jpg", "https://cdn.example.com/c.jpg"),
+            ("http://other.example/c.jpg", "http://other.example/c.jpg"),
+        ] {
+            assert_eq!(resolve(&base, location).unwrap().to_string(), expected);
+        }
+        assert!(resolve(&base, "ftp://example.com/c.jpg").is_err());
+    }
+}
@@ -0,0 +1,260 @@
+//! Consistency check between the `pictures` table and the data dir, run
+//! through `backend fsck [--repair]`, the admin API and periodically in the
+//! background. Repairs never delete picture files: orphans are moved aside
+//! and undecodable pictures go to the trash.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use libs::frame_settings::SharedSettings;
+
+use crate::{CONFIG, db::Repository, ingest, media::validate, trash};
+
+/// Hidden sub-folder of the data dir that orphan files are moved to.
+const ORPHANS_DIR: &str = ".orphans";
+/// Keeps the first full background check, which decodes every picture, out
+/// of the busy startup. A quick one that skips decoding runs right away.
+const STARTUP_DELAY: Duration = Duration::from_secs(60 * 60);
+/// Files younger than this may belong to an upload that is still being
+/// registered, so they are not orphans yet.
+const MIN_ORPHAN_AGE: Duration = Duration::from_secs(60);
+
+/// Keeps two checks from repairing the same things at once.
+static RUNNING: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    /// Files no picture refers to, relative to the data dir.
+    pub orphan_files: Vec<String>,
+    /// Pictures whose file is gone.
+    pub missing_files: Vec<Problem>,
+    /// Pictures whose file cannot be decoded.
+    pub undecodable: Vec<Problem>,
+    /// Whether the problems above were repaired.
+    pub repaired: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    pub id: String,
+    pub filename: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.orphan_files.is_empty() && self.missing_files.is_empty() && self.undecodable.is_empty()
+    }
+}
+
+/// Look for orphan files, pictures without a file and pictures that cannot
+/// be decoded, and fix them if `repair` is set:
+///
+/// - orphan files are moved to the `.orphans` folder of the data dir,
+/// - pictures without a file are removed,
+/// - undecodable pictures are moved to the trash.
+///
+/// A repair leaves alone files that are registered meanwhile, as long as
+/// this process registers them; see [`ingest::STORING`].
+pub async fn check(repo: &Repository, settings: &SharedSettings, repair: bool) -> Result<Report> {
+    let _running = RUNNING.lock().await;
+    let mut report = scan(repo, true).await?;
+    publish(&report, true);
+
+    if repair && !report.is_clean() {
+        fix(repo, settings, &report).await?;
+        report.repaired = true;
+    }
+    Ok(report)
+}
+
+/// Set the gauges from `report`. Without `decoded`, undecodable pictures were
+/// not looked for and their count is unknown.
+fn publish(report: &Report, decoded: bool) {
+    metrics::gauge!("pictureframe_fsck_orphan_files").set(report.orphan_files.len() as f64);
+    metrics::gauge!("pictureframe_fsck_missing_files").set(report.missing_files.len() as f64);
+    let undecodable = if decoded {
+        report.undecodable.len() as f64
+    } else {
+        f64::NAN
+    };
+    metrics::gauge!("pictureframe_fsck_undecodable_pictures").set(undecodable);
+}
+
+/// Find the problems [`check`] looks for; undecodable pictures only if
+/// `decode` is set.
+async fn scan(repo: &Repository, decode: bool) -> Result<Report> {
+    let data_dir = PathBuf::from(&CONFIG.backend_data_dir);
+    let mut report = Report::default();
+
+    let trashed: HashSet<String> = repo
+        .list_trashed_pictures()
+        .await?
+        .into_iter()
+        .map(|p| p.filename)
+        .collect();
+    let live = repo
+        .list_pictures(&Default::default(), Default::default(), None, None)
+        .await?;
+    let known: HashSet<String> = live.iter().map(|p| p.filename.clone()).collect();
+
+    report.orphan_files = orphans(&data_dir, &known).await?;
+    for name in orphans(&data_dir.join(trash::TRASH_DIR), &trashed).await? {
+        report
+            .orphan_files
+            .push(format!("{}/{name}", trash::TRASH_DIR));
+    }
+
+    for picture in live {
+        let path = data_dir.join(&picture.filename);
+        let problem = |error| Problem {
+            id: picture.id.clone(),
+            filename: picture.filename.clone(),
+            error,
+        };
+        match tokio::fs::try_exists(&path).await {
+            Ok(true) => {}
+            Ok(false) => {
+                report.missing_files.push(problem(None));
+                continue;
+            }
+            Err(e) => return Err(e).with_context(|| format!("cannot check {path:?}")),
+        }
+        if !decode {
+            continue;
+        }
+        let decoded = tokio::task::spawn_blocking(move || validate::check_decodable(&path)).await?;
+        if let Err(e) = decoded {
+            report.undecodable.push(problem(Some(e.to_string())));
+        }
+    }
+
+    Ok(report)
+}
+
+/// Files directly in `dir` whose name is not in `known`. Hidden files and
+/// folders belong to the backend.
+async fn orphans(dir: &Path, known: &HashSet<String>) -> Result<Vec<String>> {
+    let mut found = Vec::new();
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(found),
+        Err(e) => return Err(e).with_context(|| format!("cannot list {dir:?}")),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let meta = entry.metadata().await?;
+        let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+            continue;
+        };
+        if !meta.is_file() || name.starts_with('.') || known.contains(&name) {
+            continue;
+        }
+        let age = meta
+            .modified()
+            .ok()
+            .and_then(|m| SystemTime::now().duration_since(m).ok());
+        if age.is_some_and(|age| age >= MIN_ORPHAN_AGE) {
+            found.push(name);
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Repair what `report` found, skipping whatever changed since; the library
+/// stays in use meanwhile.
+async fn fix(repo: &Repository, settings: &SharedSettings, report: &Report) -> Result<()> {
+    let data_dir = Path::new(&CONFIG.backend_data_dir);
+    let orphans_dir = data_dir.join(ORPHANS_DIR);
+    tokio::fs::create_dir_all(&orphans_dir).await?;
+    {
+        let _storing = ingest::STORING.write().await;
+        for name in &report.orphan_files {
+            // trashed, restored or registered since
+            let filename = name.rsplit('/').next().unwrap_or(name);
+            if repo.is_filename_used(filename).await? {
+                continue;
+            }
+            let from = data_dir.join(name);
+            let to = orphans_dir.join(name.replace('/', "_"));
+            match tokio::fs::rename(&from, &to).await {
+                Ok(()) => tracing::info!("moved orphan {name} to {ORPHANS_DIR}"),
+                Err(e) => tracing::error!("cannot move {from:?} to {to:?}: {e}"),
+            }
+        }
+    }
+
+    // the file is gone already, so only the row moves to the trash
+    let mut ids = Vec::new();
+    for problem in &report.missing_files {
+        if tokio::fs::try_exists(data_dir.join(&problem.filename)).await? {
+            continue;
+        }
+        // pictures trashed meanwhile are left for the user to restore
+        if trash::trash_picture(repo, settings, &problem.id)
+            .await?
+            .is_some()
+        {
+            ids.push(problem.id.clone());
+        }
+    }
+    let purged = trash::purge(repo, &ids).await?;
+    tracing::info!("removed {purged} pictures without a file");
+
+    for problem in &report.undecodable {
+        match trash::trash_picture(repo, settings, &problem.id).await {
+            Ok(Some(_)) => {
+                tracing::info!("moved undecodable picture {} to the trash", problem.id)
+            }
+            Ok(None) => {}
+            Err(e) => tracing::error!("cannot trash undecodable picture {}: {e:#}", problem.id),
+        }
+    }
+    Ok(())
+}
+
+/// Spawn a background job that checks the library at the configured interval
+/// without repairing it, to keep the gauges current. They are first set by a
+/// quick check at startup; the first full one follows after
+/// [`STARTUP_DELAY`].
+pub fn spawn_check(repo: Arc<Repository>, settings: SharedSettings) {
+    if CONFIG.backend_fsck_interval_hours == 0 {
+        return;
+    }
+    let interval = Duration::from_secs(CONFIG.backend_fsck_interval_hours * 60 * 60);
+    tokio::spawn(async move {
+        let quick = {
+            let _running = RUNNING.lock().await;
+            scan(&repo, false).await
+        };
+        match quick {
+            Ok(report) => publish(&report, false),
+            Err(e) => tracing::error!("library check failed: {e:#}"),
+        }
+
+        let start = tokio::time::Instant::now() + STARTUP_DELAY;
+        let mut tick = tokio::time::interval_at(start, interval);
+        loop {
+            tick.tick().await;
+            match check(&repo, &settings, false).await {
+                Ok(report) if !report.is_clean() => tracing::warn!(
+                    orphan_files = report.orphan_files.len(),
+                    missing_files = report.missing_files.len(),
+                    undecodable = report.undecodable.len(),
+                    "library is inconsistent; run `backend fsck --repair`"
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::error!("library check failed: {e:#}"),
+            }
+        }
+    });
+}
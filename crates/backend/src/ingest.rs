@@ -0,0 +1,291 @@
+//! The path every new picture takes from a staged file to a registered row.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use tokio::sync::RwLock;
+
+use libs::{frame_settings::SharedSettings, framing::Rotation};
+
+use crate::{
+    CONFIG,
+    common::{ApiError, ApiResult},
+    db::{Picture, Repository, is_unique_violation},
+    media::{self, derivatives, metadata, normalize, validate},
+    quota,
+};
+
+/// Hidden sub-folder of the data dir where incoming files are written until
+/// they have been validated, so the display never picks up a partial file.
+pub const STAGING_DIR: &str = ".incoming";
+
+/// Held from moving a new file into the data dir until its row is inserted,
+/// so a repair never takes the file for an orphan in between.
+pub static STORING: RwLock<()> = RwLock::const_new(());
+
+/// Create an empty file in the staging area and return its path and handle.
+pub async fn create_staging_file() -> anyhow::Result<(PathBuf, tokio::fs::File)> {
+    let dir = Path::new(&CONFIG.backend_data_dir).join(STAGING_DIR);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("cannot create staging dir {dir:?}"))?;
+    let path = dir.join(format!("{}.part", uuid::Uuid::new_v4()));
+    let file = tokio::fs::File::create(&path)
+        .await
+        .with_context(|| format!("cannot open {path:?}"))?;
+    Ok((path, file))
+}
+
+/// Remove whatever a previous run left in the staging area, e.g. after a
+/// crash mid-upload. Only call this before anything is being staged.
+pub async fn clear_staging() {
+    let dir = Path::new(&CONFIG.backend_data_dir).join(STAGING_DIR);
+    let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+        return;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        tracing::info!("removing leftover staged file {}", path.display());
+        discard(&path).await;
+    }
+}
+
+/// Copy a file from elsewhere, e.g. the ingest folder, into the staging area.
+pub async fn stage_copy(src: &Path) -> anyhow::Result<PathBuf> {
+    let (path, mut file) = create_staging_file().await?;
+    let copied = async {
+        let mut from = tokio::fs::File::open(src).await?;
+        tokio::io::copy(&mut from, &mut file).await?;
+        file.sync_all().await
+    }
+    .await;
+    if let Err(e) = copied {
+        discard(&path).await;
+        return Err(anyhow::Error::new(e).context(format!("cannot copy {src:?}")));
+    }
+    Ok(path)
+}
+
+/// Remove a staged file that will not be registered.
+pub async fn discard(staged: &Path) {
+    if let Err(e) = tokio::fs::remove_file(staged).await
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::error!("failed to remove staged file {staged:?}: {e}");
+    }
+}
+
+/// Outcome of registering a staged file.
+pub enum Ingested {
+    Created(Picture),
+    /// The same content is already in the library; nothing new was stored.
+    Duplicate(Picture),
+}
+
+/// Validate a staged file, move it into the data dir under a fresh UUID name
+/// and insert its row; `source_url` is where it was downloaded from, if it
+/// was. Unless `allow_duplicate` is set, content that is already in the
+/// library is not stored again, and nothing is stored beyond the quotas. The
+/// staged file is consumed either way.
+pub async fn register(
+    repo: &Repository,
+    settings: &SharedSettings,
+    staged: &Path,
+    declared: Option<mime::Mime>,
+    source_url: Option<&str>,
+    allow_duplicate: bool,
+) -> ApiResult<Ingested> {
+    let path = staged.to_owned();
+    let checked = tokio::task::spawn_blocking(move || {
+        let image = validate::validate(&path, declared.as_ref())?;
+        let hash = media::hash_file(&path)?;
+        Ok::<_, validate::ValidationError>((image, hash))
+    })
+    .await
+    .map_err(|e| ApiError::Internal(e.into()))?;
+    let (image, content_hash) = match checked {
+        Ok(checked) => checked,
+        Err(e) => {
+            tracing::warn!("rejected {}: {e}", staged.display());
+            discard(staged).await;
+            return Err(e.into());
+        }
+    };
+
+    if !allow_duplicate {
+        match find_duplicate(repo, &content_hash).await {
+            Ok(None) => {}
+            Ok(Some(existing)) => {
+                discard(staged).await;
+                return Ok(Ingested::Duplicate(existing));
+            }
+            Err(e) => {
+                discard(staged).await;
+                return Err(e);
+            }
+        }
+    }
+
+    let file_id = uuid::Uuid::new_v4();
+    let (source, ext) = if CONFIG.backend_normalize_uploads {
+        let src = staged.to_owned();
+        let out = staged.with_extension("normalized");
+        let dest = out.clone();
+        let normalized = tokio::task::spawn_blocking(move || normalize::normalize(&src, &dest))
+            .await
+            .map_err(|e| ApiError::Internal(e.into()))?;
+        let format = match normalized {
+            Ok(format) => format,
+            Err(e) => {
+                discard(&out).await;
+                discard(staged).await;
+                return Err(ApiError::Internal(e.context("normalizing upload")));
+            }
+        };
+        // hashes backfilled without the original cover the normalized file
+        if !allow_duplicate {
+            let found = match hash(&out).await {
+                Ok(normalized_hash) => find_duplicate(repo, &normalized_hash).await,
+                Err(e) => Err(e),
+            };
+            match found {
+                Ok(None) => {}
+                Ok(Some(existing)) => {
+                    discard(&out).await;
+                    discard(staged).await;
+                    return Ok(Ingested::Duplicate(existing));
+                }
+                Err(e) => {
+                    discard(&out).await;
+                    discard(staged).await;
+                    return Err(e);
+                }
+            }
+        }
+        (out, format.extensions_str()[0])
+    } else {
+        (staged.to_owned(), image.extension())
+    };
+
+    // the file to store and, after normalization, the upload it came from
+    let sizes = async {
+        let stored = file_size(&source).await?;
+        let original = if source != staged {
+            file_size(staged).await?
+        } else {
+            0
+        };
+        Ok::<_, std::io::Error>((stored, original))
+    };
+    let (stored, original) = match sizes.await {
+        Ok(sizes) => sizes,
+        Err(e) => {
+            discard(&source).await;
+            discard(staged).await;
+            return Err(ApiError::Internal(e.into()));
+        }
+    };
+    let size = if CONFIG.backend_keep_originals {
+        stored + original
+    } else {
+        stored
+    };
+    let _reservation = match quota::reserve(repo, settings, size, stored + original).await {
+        Ok(reservation) => reservation,
+        Err(e) => {
+            discard(&source).await;
+            discard(staged).await;
+            return Err(e);
+        }
+    };
+
+    let filename = format!("{file_id}.{ext}");
+    let dest = Path::new(&CONFIG.backend_data_dir).join(&filename);
+    let _storing = STORING.read().await;
+    if let Err(e) = tokio::fs::rename(&source, &dest).await {
+        discard(&source).await;
+        discard(staged).await;
+        return Err(ApiError::Internal(
+            anyhow::Error::new(e).context(format!("cannot move {source:?} to {dest:?}")),
+        ));
+    }
+    tracing::debug!("saved {}", dest.display());
+
+    if source != staged {
+        keep_or_discard_original(staged, &filename, image.extension()).await;
+    }
+
+    // describe the file as stored, so GPS stripped by normalization stays gone
+    let stored = dest.clone();
+    let metadata = tokio::task::spawn_blocking(move || metadata::extract(&stored))
+        .await
+        .map_err(|e| ApiError::Internal(e.into()))?;
+
+    let added = repo
+        .add_picture(
+            &filename,
+            &content_hash,
+            metadata,
+            source_url,
+            allow_duplicate,
+        )
+        .await;
+    let saved = match added {
+        Ok(saved) => saved,
+        Err(e) => {
+            discard(&dest).await;
+            normalize::remove_original(&filename).await;
+            // the same content was registered concurrently
+            if is_unique_violation(&e)
+                && let Some(existing) = repo.find_picture_by_hash(&content_hash).await?
+            {
+                return Ok(Ingested::Duplicate(existing));
+            }
+            return Err(e.into());
+        }
+    };
+    derivatives::spawn_generate(filename, Rotation::default());
+
+    Ok(Ingested::Created(saved))
+}
+
+async fn file_size(path: &Path) -> std::io::Result<u64> {
+    Ok(tokio::fs::metadata(path).await?.len())
+}
+
+async fn hash(path: &Path) -> ApiResult<String> {
+    let path = path.to_owned();
+    tokio::task::spawn_blocking(move || media::hash_file(&path))
+        .await
+        .map_err(|e| ApiError::Internal(e.into()))?
+        .map_err(|e| ApiError::Internal(e.into()))
+}
+
+async fn find_duplicate(repo: &Repository, content_hash: &str) -> ApiResult<Option<Picture>> {
+    let existing = repo.find_picture_by_hash(content_hash).await?;
+    if let Some(existing) = &existing {
+        tracing::info!("duplicate of {} ({})", existing.id, existing.filename);
+    }
+    Ok(existing)
+}
+
+/// After normalization, move the untouched upload aside if configured to,
+/// otherwise drop it.
+async fn keep_or_discard_original(staged: &Path, filename: &str, ext: &str) {
+    if !CONFIG.backend_keep_originals {
+        discard(staged).await;
+        return;
+    }
+    let kept = normalize::original_path(filename, ext);
+    let moved = match kept.parent() {
+        Some(dir) => match tokio::fs::create_dir_all(dir).await {
+            Ok(()) => tokio::fs::rename(staged, &kept).await,
+            Err(e) => Err(e),
+        },
+        None => Ok(()),
+    };
+    if let Err(e) = moved {
+        tracing::error!("cannot keep original {staged:?} as {kept:?}: {e}");
+        discard(staged).await;
+    }
+}
@@ -0,0 +1,181 @@
+//! Imports pictures dropped into the ingest folder, e.g. copied over SSH,
+//! the same way as uploads. Rejected files are moved to a quarantine folder.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher};
+use tokio::{sync::mpsc, time::Instant};
+
+use libs::frame_settings::SharedSettings;
+
+use crate::{
+    CONFIG,
+    common::ApiError,
+    db::Repository,
+    ingest::{self, Ingested},
+};
+
+/// Sub-folder of the ingest folder that rejected files are moved to, each
+/// with a `.reason` file next to it.
+const QUARANTINE_DIR: &str = "quarantine";
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Last seen state of a file that is waiting to be imported.
+struct Settling {
+    size: Option<u64>,
+    modified: Option<SystemTime>,
+    /// When the file was last seen changing.
+    since: Instant,
+}
+
+impl Settling {
+    fn new() -> Self {
+        Settling {
+            size: None,
+            modified: None,
+            since: Instant::now(),
+        }
+    }
+}
+
+/// Watch the configured ingest folder, if any, and import what appears in
+/// it. Files already there are imported right away.
+pub fn spawn_watcher(repo: Arc<Repository>, settings: SharedSettings) -> Result<()> {
+    let Some(dir) = &CONFIG.backend_ingest_dir else {
+        return Ok(());
+    };
+    let dir = PathBuf::from(dir);
+    let quarantine = dir.join(QUARANTINE_DIR);
+    std::fs::create_dir_all(&quarantine).with_context(|| format!("creating {quarantine:?}"))?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) => event.paths.into_iter().for_each(|p| {
+                let _ = tx.send(p);
+            }),
+            Err(e) => tracing::warn!("ingest folder watch error: {e}"),
+        })?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("watching {dir:?}"))?;
+    tracing::info!("watching ingest folder {}", dir.display());
+
+    let mut pending: HashMap<PathBuf, Settling> = std::fs::read_dir(&dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| is_candidate(&dir, p))
+        .map(|p| (p, Settling::new()))
+        .collect();
+
+    tokio::spawn(async move {
+        // dropping the watcher would stop the events
+        let _watcher = watcher;
+        let settle = Duration::from_secs(CONFIG.backend_ingest_settle_secs);
+        let mut tick = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                Some(path) = rx.recv() => {
+                    if is_candidate(&dir, &path) {
+                        pending.entry(path).or_insert_with(Settling::new);
+                    }
+                }
+                _ = tick.tick() => {
+                    for path in settled(&mut pending, settle).await {
+                        import(&repo, &settings, &dir, &path).await;
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Files directly in the ingest folder, except hidden ones such as the
+/// temporary files of `rsync`.
+fn is_candidate(dir: &Path, path: &Path) -> bool {
+    path.parent() == Some(dir)
+        && path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| !n.starts_with('.') && n != QUARANTINE_DIR)
+}
+
+/// Take the files that have not changed for `settle` out of `pending`. Files
+/// that are gone or turned out not to be files are forgotten.
+async fn settled(pending: &mut HashMap<PathBuf, Settling>, settle: Duration) -> Vec<PathBuf> {
+    let mut ready = Vec::new();
+    let mut gone = Vec::new();
+    for (path, seen) in pending.iter_mut() {
+        let meta = match tokio::fs::metadata(path).await {
+            Ok(meta) if meta.is_file() => meta,
+            _ => {
+                gone.push(path.clone());
+                continue;
+            }
+        };
+        let (size, modified) = (Some(meta.len()), meta.modified().ok());
+        if (size, modified) != (seen.size, seen.modified) {
+            seen.size = size;
+            seen.modified = modified;
+            seen.since = Instant::now();
+        } else if seen.since.elapsed() >= settle {
+            ready.push(path.clone());
+        }
+    }
+    for path in gone.iter().chain(&ready) {
+        pending.remove(path);
+    }
+    ready
+}
+
+async fn import(repo: &Repository, settings: &SharedSettings, dir: &Path, path: &Path) {
+    let staged = match ingest::stage_copy(path).await {
+        Ok(staged) => staged,
+        Err(e) => {
+            tracing::error!("cannot import {}: {e:#}", path.display());
+            return;
+        }
+    };
+    match ingest::register(repo, settings, &staged, None, None, false).await {
+        Ok(Ingested::Created(picture)) => {
+            tracing::info!("imported {} as {}", path.display(), picture.filename);
+            if let Err(e) = tokio::fs::remove_file(path).await {
+                tracing::error!("cannot remove imported {}: {e}", path.display());
+            }
+        }
+        Ok(Ingested::Duplicate(existing)) => {
+            quarantine(dir, path, &format!("duplicate of picture {}", existing.id)).await;
+        }
+        // not the file's fault; it stays and is retried on the next start
+        Err(ApiError::Internal(e)) => {
+            tracing::error!("cannot import {}: {e:#}", path.display());
+        }
+        Err(e) => quarantine(dir, path, &e.to_string()).await,
+    }
+}
+
+/// Move a rejected file out of the way, noting why next to it.
+async fn quarantine(dir: &Path, path: &Path, reason: &str) {
+    tracing::warn!("quarantining {}: {reason}", path.display());
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut dest = dir.join(QUARANTINE_DIR).join(name.as_ref());
+    if tokio::fs::try_exists(&dest).await.unwrap_or(false) {
+        dest.set_file_name(format!("{}-{name}", uuid::Uuid::new_v4()));
+    }
+    if let Err(e) = tokio::fs::rename(path, &dest).await {
+        tracing::error!("cannot move {} to {}: {e}", path.display(), dest.display());
+        return;
+    }
+    let mut note = dest.into_os_string();
+    note.push(".reason");
+    if let Err(e) = tokio::fs::write(&note, format!("{reason}\n")).await {
+        tracing::error!("cannot write {note:?}: {e}");
+    }
+}
@@ -1,6 +1,18 @@
 pub mod api;
+pub mod backup;
 pub mod common;
 mod config;
 pub mod db;
+pub mod fetch;
+pub mod fsck;
+pub mod ingest;
+pub mod ingest_folder;
+pub mod library;
+pub mod maintenance;
+pub mod media;
+pub mod quota;
+pub mod trash;
+pub mod uploads;
+pub mod webdav;
 
 pub use config::CONFIG;
@@ -0,0 +1,87 @@
+//! Keeps the library index file the display reads in sync with the database.
+
+use std::{path::Path, sync::Arc};
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use tokio::sync::Notify;
+
+use libs::library_index::{AlbumEntry, LibraryIndex};
+
+use crate::{CONFIG, db::Repository};
+
+static CHANGED: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// Schedule a rewrite of the index after albums, memberships, tags,
+/// captions, weights, picture schedules, framing or rotations changed.
+/// Changes made in quick succession are written once.
+pub fn changed() {
+    CHANGED.notify_one();
+}
+
+/// Spawn the background job that writes the index at startup and after every
+/// [`changed`] call.
+pub fn spawn_publisher(repo: Arc<Repository>) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = publish(&repo).await {
+                tracing::error!("cannot write library index: {e:#}");
+            }
+            CHANGED.notified().await;
+        }
+    });
+}
+
+async fn publish(repo: &Repository) -> Result<()> {
+    let mut index = LibraryIndex::default();
+    for album in repo.list_albums().await? {
+        index.albums.insert(
+            album.id,
+            AlbumEntry {
+                name: album.name,
+                pictures: Vec::new(),
+            },
+        );
+    }
+    for (album_id, filename) in repo.list_album_memberships().await? {
+        if let Some(album) = index.albums.get_mut(&album_id) {
+            album.pictures.push(filename);
+        }
+    }
+
+    for (filename, tag) in repo.list_picture_tags().await? {
+        index.pictures.entry(filename).or_default().tags.push(tag);
+    }
+
+    for (filename, title, caption) in repo.list_picture_texts().await? {
+        let entry = index.pictures.entry(filename).or_default();
+        entry.title = title;
+        entry.caption = caption;
+    }
+
+    for (filename, favorite, weight) in repo.list_picture_weightings().await? {
+        let entry = index.pictures.entry(filename).or_default();
+        entry.favorite = favorite;
+        entry.weight = (weight != 1).then_some(weight);
+    }
+
+    for (filename, schedule) in repo.list_picture_schedules().await? {
+        index.pictures.entry(filename).or_default().schedule = schedule;
+    }
+
+    for (filename, crop, focal_point) in repo.list_picture_framings().await? {
+        let entry = index.pictures.entry(filename).or_default();
+        entry.crop = crop;
+        entry.focal_point = focal_point;
+    }
+
+    for (filename, rotation) in repo.list_picture_rotations().await? {
+        index.pictures.entry(filename).or_default().rotation = rotation;
+    }
+
+    let albums = index.albums.len();
+    tokio::task::spawn_blocking(move || index.write(Path::new(&CONFIG.backend_library_index_file)))
+        .await??;
+    tracing::debug!(albums, "library index written");
+    Ok(())
+}
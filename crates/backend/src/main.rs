@@ -1,22 +1,25 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 use anyhow::Result;
-use axum::{Router, extract::Request, middleware};
+use axum::{
+    Router,
+    extract::{DefaultBodyLimit, Request},
+    middleware,
+};
 use r2d2_sqlite::SqliteConnectionManager;
 use tokio::{net::TcpListener, sync::Notify};
-use tower_http::{
-    limit::RequestBodyLimitLayer,
-    trace::{DefaultOnFailure, DefaultOnRequest, DefaultOnResponse, TraceLayer},
-};
+use tower_http::trace::{DefaultOnFailure, DefaultOnRequest, DefaultOnResponse, TraceLayer};
 use tracing::Level;
 use tracing_subscriber::EnvFilter;
 
 use libs::{frame_settings::SharedSettings, util};
 
 use backend::{
-    CONFIG, api,
+    CONFIG, api, backup,
     common::{AppState, metrics},
     db::Repository,
+    fetch::Fetcher,
+    fsck, ingest, ingest_folder, library, maintenance, trash, uploads, webdav,
 };
 
 #[tokio::main]
@@ -27,19 +30,74 @@ async fn main() -> Result<()> {
         .with_env_filter(EnvFilter::from_env("LOG_LEVEL"))
         .init();
 
+    let args: Vec<String> = std::env::args().collect();
+    // before anything opens the files it replaces
+    if args.get(1).map(String::as_str) == Some("restore") {
+        let Some(archive) = args.get(2) else {
+            anyhow::bail!("usage: backend restore <archive>");
+        };
+        let _port = ensure_stopped("restoring")?;
+        let aside = backup::restore(Path::new(archive))?;
+        tracing::info!(
+            "restored {archive}; the previous state is in {}",
+            aside.display()
+        );
+        return Ok(());
+    }
+
     let shared_settings = SharedSettings::load(&CONFIG.backend_frame_settings_file).unwrap();
-    let manager = SqliteConnectionManager::file(CONFIG.backend_db_file.clone());
+    let manager = SqliteConnectionManager::file(CONFIG.backend_db_file.clone())
+        .with_init(|conn| conn.execute_batch("PRAGMA foreign_keys = ON;"));
     let pool = r2d2::Pool::builder().max_size(4).build(manager).unwrap();
     let repo = Repository::new(pool);
     repo.init_schema()?;
+
+    match args.get(1).map(String::as_str) {
+        None | Some("serve") => {}
+        Some("backfill-hashes") => {
+            let n = maintenance::backfill_hashes(&repo).await?;
+            tracing::info!("hashed {n} pictures");
+            return Ok(());
+        }
+        Some("backfill-metadata") => {
+            let n = maintenance::backfill_metadata(&repo).await?;
+            tracing::info!("extracted metadata of {n} pictures");
+            return Ok(());
+        }
+        Some("fsck") => {
+            let repair = match args.get(2).map(String::as_str) {
+                None => false,
+                Some("--repair") => true,
+                Some(other) => anyhow::bail!("unknown option `{other}`"),
+            };
+            // a running backend could register a file just as it is moved
+            // aside; within the backend, ingest::STORING keeps them apart
+            let _port = if repair {
+                Some(ensure_stopped("repairing")?)
+            } else {
+                None
+            };
+            let report = fsck::check(&repo, &shared_settings, repair).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        Some(other) => anyhow::bail!("unknown command `{other}`"),
+    }
+
     let state = AppState {
         repo: Arc::new(repo),
         settings: shared_settings.clone(),
+        fetcher: Arc::new(Fetcher::from_config()),
     };
 
     let api_router = Router::new()
+        .merge(api::admin_routes())
         .merge(api::picture_routes())
         .merge(api::settings_routes())
+        .merge(api::album_routes())
+        .merge(api::bulk_routes())
+        .merge(api::trash_routes())
+        .merge(api::upload_routes())
         .with_state(state.clone())
         .route_layer(middleware::from_fn(metrics::track_http))
         .layer(
@@ -56,14 +114,10 @@ async fn main() -> Result<()> {
             .on_response(DefaultOnResponse::new().level(Level::INFO).include_headers(true))
             .on_failure(DefaultOnFailure::new().level(Level::INFO))
         )
-        .layer(RequestBodyLimitLayer::new(10 * 1024 * 1024 /* 10MiB */));
+        // default for extractors; uploads raise it per route
+        .layer(DefaultBodyLimit::max(10 * 1024 * 1024 /* 10MiB */));
     let metrics_router = metrics::prometheus_router();
 
-    metrics::spawn_system_metrics(state.repo.clone());
-
-    let shutdown_notify = Arc::new(Notify::new());
-    tokio::spawn(util::listen_for_shutdown(shutdown_notify.clone()));
-
     let api_listener = TcpListener::bind(format!(
         "{}:{}",
         CONFIG.backend_ipv4_address, CONFIG.backend_port
@@ -75,6 +129,18 @@ async fn main() -> Result<()> {
     ))
     .await?;
 
+    // bound, so no other instance is staging files
+    ingest::clear_staging().await;
+    metrics::spawn_system_metrics(state.repo.clone());
+    uploads::spawn_expiry(state.repo.clone());
+    library::spawn_publisher(state.repo.clone());
+    trash::spawn_purge(state.repo.clone());
+    fsck::spawn_check(state.repo.clone(), state.settings.clone());
+    ingest_folder::spawn_watcher(state.repo.clone(), state.settings.clone())?;
+
+    let shutdown_notify = Arc::new(Notify::new());
+    tokio::spawn(util::listen_for_shutdown(shutdown_notify.clone()));
+
     tracing::info!("⇢ API listening on: http://{}", api_listener.local_addr()?);
     tracing::info!(
         "⇢ Metrics listening on: http://{}/metrics",
@@ -90,7 +156,23 @@ async fn main() -> Result<()> {
         async move { n.notified().await }
     });
 
-    tokio::try_join!(api_server, metrics_server)?;
+    let webdav_server = webdav::serve(state, {
+        let n = shutdown_notify.clone();
+        async move { n.notified().await }
+    });
+
+    tokio::try_join!(api_server, metrics_server, webdav_server)?;
 
     Ok(())
 }
+
+/// Refuse to work under a running backend, detected by its API port being
+/// taken. The port stays bound until the returned listener is dropped, so
+/// the backend cannot start in the meantime.
+fn ensure_stopped(action: &str) -> Result<std::net::TcpListener> {
+    let addr = format!("{}:{}", CONFIG.backend_ipv4_address, CONFIG.backend_port);
+    match std::net::TcpListener::bind(&addr) {
+        Ok(listener) => Ok(listener),
+        Err(_) => anyhow::bail!("{addr} is in use; stop the backend before {action}"),
+    }
+}
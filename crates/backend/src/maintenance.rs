@@ -0,0 +1,64 @@
+//! One-off maintenance jobs, run through `backend <command>`.
+
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{
+    CONFIG,
+    db::Repository,
+    media::{self, normalize},
+};
+
+/// Record the SHA-256 of every picture stored before hashes were tracked.
+/// The kept original is hashed if there is one, since that is what a
+/// re-upload would match. Returns the number of rows updated.
+pub async fn backfill_hashes(repo: &Repository) -> Result<usize> {
+    let pending = repo.list_unhashed_pictures().await?;
+    tracing::info!(count = pending.len(), "pictures without content hash");
+
+    let mut updated = 0;
+    for pic in pending {
+        let filename = pic.filename.clone();
+        let hashed = tokio::task::spawn_blocking(move || {
+            let path = normalize::find_original(&filename)
+                .unwrap_or_else(|| Path::new(&CONFIG.backend_data_dir).join(&filename));
+            media::hash_file(&path)
+        });
+        let hash = match hashed.await? {
+            Ok(hash) => hash,
+            Err(e) => {
+                tracing::warn!("cannot hash {} ({}): {e}", pic.id, pic.filename);
+                continue;
+            }
+        };
+        if let Some(existing) = repo.find_picture_by_hash(&hash).await? {
+            tracing::info!("{} is a duplicate of {}", pic.id, existing.id);
+        }
+        repo.set_content_hash(&pic.id, &hash).await?;
+        updated += 1;
+    }
+
+    Ok(updated)
+}
+
+/// Extract EXIF metadata for every picture stored before it was recorded.
+/// Returns the number of rows updated.
+pub async fn backfill_metadata(repo: &Repository) -> Result<usize> {
+    let pending = repo.list_pictures_without_metadata().await?;
+    tracing::info!(count = pending.len(), "pictures without metadata");
+
+    let mut updated = 0;
+    for pic in pending {
+        let path = Path::new(&CONFIG.backend_data_dir).join(&pic.filename);
+        if !path.exists() {
+            tracing::warn!("file of {} is missing: {}", pic.id, path.display());
+            continue;
+        }
+        let meta = tokio::task::spawn_blocking(move || media::metadata::extract(&path)).await?;
+        repo.set_picture_metadata(&pic.id, meta).await?;
+        updated += 1;
+    }
+
+    Ok(updated)
+}
@@ -0,0 +1,141 @@
+use std::{
+    fs,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, codecs::jpeg::JpegEncoder};
+use serde::Deserialize;
+
+use libs::framing::Rotation;
+
+use crate::CONFIG;
+
+/// Hidden sub-folder of the data dir holding the cached derivatives. The
+/// display only scans the top level of the data dir, so it never sees them.
+const DERIVATIVES_DIR: &str = ".derivatives";
+const JPEG_QUALITY: u8 = 85;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DerivativeSize {
+    #[default]
+    #[serde(alias = "256")]
+    Thumbnail,
+    #[serde(alias = "1024")]
+    Preview,
+}
+
+impl DerivativeSize {
+    pub const ALL: [DerivativeSize; 2] = [DerivativeSize::Thumbnail, DerivativeSize::Preview];
+
+    /// Longest edge in pixels.
+    pub fn max_edge(self) -> u32 {
+        match self {
+            DerivativeSize::Thumbnail => 256,
+            DerivativeSize::Preview => 1024,
+        }
+    }
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            DerivativeSize::Thumbnail => "thumbnail",
+            DerivativeSize::Preview => "preview",
+        }
+    }
+}
+
+/// Where the derivative of `filename` is cached, e.g. `.derivatives/thumbnail/<uuid>.jpg`.
+pub fn derivative_path(filename: &str, size: DerivativeSize) -> PathBuf {
+    let stem = Path::new(filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(filename);
+    Path::new(&CONFIG.backend_data_dir)
+        .join(DERIVATIVES_DIR)
+        .join(size.dir_name())
+        .join(format!("{stem}.jpg"))
+}
+
+/// Decode the original once and write every derivative size. Blocking.
+pub fn generate_all(filename: &str, rotation: Rotation) -> Result<()> {
+    let img = load_original(filename, rotation)?;
+    for size in DerivativeSize::ALL {
+        write_derivative(&img, &derivative_path(filename, size), size)?;
+    }
+    Ok(())
+}
+
+/// Return the cached derivative, generating it first if it does not exist yet. Blocking.
+pub fn ensure(filename: &str, size: DerivativeSize, rotation: Rotation) -> Result<PathBuf> {
+    let path = derivative_path(filename, size);
+    if path.exists() {
+        return Ok(path);
+    }
+    let img = load_original(filename, rotation)?;
+    write_derivative(&img, &path, size)?;
+    Ok(path)
+}
+
+/// Generate all derivatives on a blocking thread without waiting for the result.
+pub fn spawn_generate(filename: String, rotation: Rotation) {
+    tokio::task::spawn_blocking(move || match generate_all(&filename, rotation) {
+        Ok(()) => tracing::debug!("generated derivatives for {filename}"),
+        Err(e) => tracing::error!("failed to generate derivatives for {filename}: {e:#}"),
+    });
+}
+
+/// Remove every cached derivative of `filename`.
+pub async fn remove(filename: &str) {
+    for size in DerivativeSize::ALL {
+        let path = derivative_path(filename, size);
+        match tokio::fs::remove_file(&path).await {
+            Ok(_) => tracing::debug!("removed derivative {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::error!("failed to delete {path:?}: {e}"),
+        }
+    }
+}
+
+/// Drop the cached derivatives of `filename` and make new ones, e.g. after
+/// its rotation changed.
+pub async fn regenerate(filename: String, rotation: Rotation) {
+    remove(&filename).await;
+    spawn_generate(filename, rotation);
+}
+
+fn load_original(filename: &str, rotation: Rotation) -> Result<DynamicImage> {
+    let original = Path::new(&CONFIG.backend_data_dir).join(filename);
+    let img = super::load_oriented(&original).with_context(|| format!("decoding {original:?}"))?;
+    Ok(rotation.apply(img))
+}
+
+fn write_derivative(img: &DynamicImage, path: &Path, size: DerivativeSize) -> Result<()> {
+    let dir = path.parent().expect("derivative path has a parent");
+    fs::create_dir_all(dir).with_context(|| format!("creating {dir:?}"))?;
+
+    let edge = size.max_edge();
+    let scaled = if img.width() > edge || img.height() > edge {
+        img.thumbnail(edge, edge)
+    } else {
+        img.clone()
+    };
+
+    // write to a unique temp file first so readers never see a partial JPEG
+    let tmp = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let written = encode_jpeg(&scaled, &tmp)
+        .and_then(|()| fs::rename(&tmp, path).with_context(|| format!("renaming {tmp:?}")));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written
+}
+
+fn encode_jpeg(img: &DynamicImage, path: &Path) -> Result<()> {
+    let file = fs::File::create(path).with_context(|| format!("creating {path:?}"))?;
+    let encoder = JpegEncoder::new_with_quality(BufWriter::new(file), JPEG_QUALITY);
+    img.to_rgb8()
+        .write_with_encoder(encoder)
+        .with_context(|| format!("encoding {path:?}"))
+}
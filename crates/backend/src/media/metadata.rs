@@ -0,0 +1,114 @@
+use std::{fs, io::Cursor, path::Path};
+
+use chrono::{FixedOffset, NaiveDate, TimeZone, Utc};
+use exif::{Exif, In, Tag, Value};
+
+use crate::db::PictureMetadata;
+
+/// Read dimensions from the image header and capture details from EXIF.
+/// Missing or malformed fields are left empty. Blocking.
+pub fn extract(path: &Path) -> PictureMetadata {
+    let (width, height) = match image::image_dimensions(path) {
+        Ok((w, h)) => (Some(w), Some(h)),
+        Err(e) => {
+            tracing::warn!("cannot read dimensions of {path:?}: {e}");
+            (None, None)
+        }
+    };
+
+    let exif = fs::read(path).ok().and_then(|bytes| {
+        exif::Reader::new()
+            .read_from_container(&mut Cursor::new(bytes))
+            .ok()
+    });
+    let Some(exif) = exif else {
+        return PictureMetadata {
+            width,
+            height,
+            ..Default::default()
+        };
+    };
+
+    PictureMetadata {
+        taken_at: taken_at(&exif),
+        camera_make: ascii(&exif, Tag::Make),
+        camera_model: ascii(&exif, Tag::Model),
+        width,
+        height,
+        orientation: exif
+            .get_field(Tag::Orientation, In::PRIMARY)
+            .and_then(|f| f.value.get_uint(0))
+            .and_then(|o| u16::try_from(o).ok()),
+        gps_latitude: coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S'),
+        gps_longitude: coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W'),
+    }
+}
+
+fn ascii(exif: &Exif, tag: Tag) -> Option<String> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(parts) => {
+            let s = String::from_utf8_lossy(parts.first()?);
+            let s = s.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+            (!s.is_empty()).then(|| s.to_owned())
+        }
+        _ => None,
+    }
+}
+
+/// `DateTimeOriginal`, falling back to the digitized and modified times.
+fn taken_at(exif: &Exif) -> Option<i64> {
+    [
+        (Tag::DateTimeOriginal, Tag::OffsetTimeOriginal),
+        (Tag::DateTimeDigitized, Tag::OffsetTimeDigitized),
+        (Tag::DateTime, Tag::OffsetTime),
+    ]
+    .into_iter()
+    .find_map(|(tag, offset_tag)| {
+        let Value::Ascii(parts) = &exif.get_field(tag, In::PRIMARY)?.value else {
+            return None;
+        };
+        let mut dt = exif::DateTime::from_ascii(parts.first()?).ok()?;
+        if let Some(Value::Ascii(offset)) =
+            exif.get_field(offset_tag, In::PRIMARY).map(|f| &f.value)
+            && let Some(offset) = offset.first()
+        {
+            dt.parse_offset(offset).ok();
+        }
+
+        let naive = NaiveDate::from_ymd_opt(dt.year.into(), dt.month.into(), dt.day.into())?
+            .and_hms_nano_opt(
+                dt.hour.into(),
+                dt.minute.into(),
+                dt.second.into(),
+                dt.nanosecond.unwrap_or(0),
+            )?;
+        let millis = match dt.offset {
+            Some(minutes) => FixedOffset::east_opt(i32::from(minutes) * 60)?
+                .from_local_datetime(&naive)
+                .single()?
+                .timestamp_millis(),
+            None => Utc.from_utc_datetime(&naive).timestamp_millis(),
+        };
+        Some(millis)
+    })
+}
+
+/// Degrees/minutes/seconds plus hemisphere reference as signed decimal degrees.
+fn coordinate(exif: &Exif, tag: Tag, ref_tag: Tag, negative_ref: u8) -> Option<f64> {
+    let Value::Rational(dms) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    let degrees = dms
+        .iter()
+        .zip([1.0, 60.0, 3600.0])
+        .map(|(r, div)| r.to_f64() / div)
+        .sum::<f64>();
+    if !degrees.is_finite() {
+        return None;
+    }
+    let negative = match &exif.get_field(ref_tag, In::PRIMARY)?.value {
+        Value::Ascii(parts) => parts.first()?.first() == Some(&negative_ref),
+        _ => false,
+    };
+    Some(if negative { -degrees } else { degrees })
+}
@@ -0,0 +1,28 @@
+pub mod derivatives;
+pub mod metadata;
+pub mod normalize;
+pub mod validate;
+
+use std::{fs::File, io, path::Path};
+
+use image::{DynamicImage, ImageDecoder, ImageReader, ImageResult};
+use sha2::{Digest, Sha256};
+
+/// Decode an image from disk with its EXIF orientation applied to the pixels.
+pub fn load_oriented(path: &Path) -> ImageResult<DynamicImage> {
+    let mut decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// Hex-encoded SHA-256 of a file's contents. Blocking.
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
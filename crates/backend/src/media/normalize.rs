@@ -0,0 +1,181 @@
+//! Optional clean-up of uploads before they are stored: bake the EXIF
+//! orientation into the pixels, cap the resolution, drop privacy-sensitive
+//! metadata and re-encode.
+
+use std::{
+    fs,
+    io::{BufWriter, Cursor},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use exif::{In, Tag, experimental::Writer as ExifWriter};
+use image::{
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader, codecs::jpeg::JpegEncoder,
+    imageops::FilterType,
+};
+
+use crate::CONFIG;
+
+/// Hidden sub-folder of the data dir keeping the untouched uploads when
+/// `BACKEND_KEEP_ORIGINALS` is set.
+const ORIGINALS_DIR: &str = ".originals";
+const JPEG_QUALITY: u8 = 90;
+
+/// Tags that identify a person or device, or no longer describe the
+/// re-encoded pixels. GPS tags are dropped wholesale.
+const DROPPED_TAGS: &[Tag] = &[
+    Tag::Orientation,
+    Tag::MakerNote,
+    Tag::BodySerialNumber,
+    Tag::LensSerialNumber,
+    Tag::CameraOwnerName,
+    Tag::ImageUniqueID,
+    Tag::ImageWidth,
+    Tag::ImageLength,
+    Tag::PixelXDimension,
+    Tag::PixelYDimension,
+];
+
+/// Normalize the image at `src` and write the result to `dest`. Returns the
+/// format it was written in. Blocking.
+///
+/// JPEGs and PNGs keep their format; other formats become PNG if they have
+/// an alpha channel and JPEG otherwise.
+pub fn normalize(src: &Path, dest: &Path) -> Result<ImageFormat> {
+    let bytes = fs::read(src).with_context(|| format!("reading {src:?}"))?;
+
+    let reader = ImageReader::new(Cursor::new(&bytes)).with_guessed_format()?;
+    let input_format = reader.format().context("unknown image format")?;
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+
+    let max_edge = CONFIG.backend_normalize_max_edge;
+    if img.width() > max_edge || img.height() > max_edge {
+        img = img.resize(max_edge, max_edge, FilterType::Lanczos3);
+    }
+
+    let format = match input_format {
+        ImageFormat::Jpeg | ImageFormat::Png => input_format,
+        _ if img.color().has_alpha() => ImageFormat::Png,
+        _ => ImageFormat::Jpeg,
+    };
+
+    match format {
+        ImageFormat::Jpeg => {
+            let mut encoded = Vec::new();
+            let encoder = JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY);
+            img.to_rgb8().write_with_encoder(encoder)?;
+            if let Some(exif) = scrubbed_exif(&bytes) {
+                encoded = insert_exif_segment(encoded, &exif);
+            }
+            fs::write(dest, encoded).with_context(|| format!("writing {dest:?}"))?;
+        }
+        _ => {
+            let file = fs::File::create(dest).with_context(|| format!("creating {dest:?}"))?;
+            img.write_to(&mut BufWriter::new(file), format)
+                .with_context(|| format!("encoding {dest:?}"))?;
+        }
+    }
+
+    Ok(format)
+}
+
+/// The original's EXIF block (TIFF layout) minus GPS, serials and other
+/// [`DROPPED_TAGS`]; `None` if it has no EXIF or nothing is left.
+fn scrubbed_exif(original: &[u8]) -> Option<Vec<u8>> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(original))
+        .ok()?;
+
+    let mut writer = ExifWriter::new();
+    let mut kept = 0;
+    for field in exif.fields() {
+        // the thumbnail IFD still shows the un-rotated, un-scaled image
+        if field.ifd_num != In::PRIMARY
+            || field.tag.context() == exif::Context::Gps
+            || DROPPED_TAGS.contains(&field.tag)
+        {
+            continue;
+        }
+        writer.push_field(field);
+        kept += 1;
+    }
+    if kept == 0 {
+        return None;
+    }
+
+    let mut out = Cursor::new(Vec::new());
+    match writer.write(&mut out, exif.little_endian()) {
+        Ok(()) => Some(out.into_inner()),
+        Err(e) => {
+            tracing::debug!("dropping EXIF that cannot be rewritten: {e}");
+            None
+        }
+    }
+}
+
+/// Put an `APP1` EXIF segment right after the JPEG's start-of-image marker.
+fn insert_exif_segment(jpeg: Vec<u8>, tiff: &[u8]) -> Vec<u8> {
+    const HEADER: &[u8] = b"Exif\0\0";
+    let len = 2 + HEADER.len() + tiff.len();
+    if len > u16::MAX as usize || !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return jpeg;
+    }
+
+    let mut out = Vec::with_capacity(jpeg.len() + len + 2);
+    out.extend_from_slice(&jpeg[..2]);
+    out.extend_from_slice(&[0xFF, 0xE1]);
+    out.extend_from_slice(&(len as u16).to_be_bytes());
+    out.extend_from_slice(HEADER);
+    out.extend_from_slice(tiff);
+    out.extend_from_slice(&jpeg[2..]);
+    out
+}
+
+/// Where the untouched upload behind `filename` is kept, e.g.
+/// `.originals/<uuid>.tiff`; `ext` is the original's extension.
+pub fn original_path(filename: &str, ext: &str) -> PathBuf {
+    let stem = Path::new(filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(filename);
+    Path::new(&CONFIG.backend_data_dir)
+        .join(ORIGINALS_DIR)
+        .join(format!("{stem}.{ext}"))
+}
+
+/// The kept original of `filename`, if there is one. Blocking.
+pub fn find_original(filename: &str) -> Option<PathBuf> {
+    let stem = Path::new(filename).file_stem()?;
+    let dir = Path::new(&CONFIG.backend_data_dir).join(ORIGINALS_DIR);
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.file_stem() == Some(stem))
+}
+
+/// Remove the kept original of `filename`, if there is one.
+pub async fn remove_original(filename: &str) {
+    let stem = Path::new(filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(filename);
+    let dir = Path::new(&CONFIG.backend_data_dir).join(ORIGINALS_DIR);
+    let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+        return;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.file_stem().and_then(|s| s.to_str()) != Some(stem) {
+            continue;
+        }
+        match tokio::fs::remove_file(&path).await {
+            Ok(_) => tracing::debug!("removed original {}", path.display()),
+            Err(e) => tracing::error!("failed to delete {path:?}: {e}"),
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use std::{io::Cursor, path::Path};
+
+use image::{ImageError, ImageFormat, ImageReader, Limits};
+use thiserror::Error;
+
+use crate::CONFIG;
+
+/// Formats we accept for storage, with the content types clients may declare for them.
+const ALLOWED_FORMATS: &[(ImageFormat, &[&str])] = &[
+    (
+        ImageFormat::Jpeg,
+        &["image/jpeg", "image/jpg", "image/pjpeg"],
+    ),
+    (ImageFormat::Png, &["image/png"]),
+    (ImageFormat::WebP, &["image/webp"]),
+    (ImageFormat::Gif, &["image/gif"]),
+    (ImageFormat::Tiff, &["image/tiff"]),
+    (
+        ImageFormat::Bmp,
+        &["image/bmp", "image/x-bmp", "image/x-ms-bmp"],
+    ),
+];
+
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("file is not a recognised image")]
+    UnknownFormat,
+    #[error("{0:?} images are not supported")]
+    UnsupportedFormat(ImageFormat),
+    #[error("declared content type {declared} does not match {actual:?} content")]
+    Mislabeled {
+        declared: String,
+        actual: ImageFormat,
+    },
+    #[error("image is {width}x{height}, larger than the allowed {max_width}x{max_height}")]
+    TooLarge {
+        width: u32,
+        height: u32,
+        max_width: u32,
+        max_height: u32,
+    },
+    #[error("image could not be decoded: {0}")]
+    Corrupt(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ValidatedImage {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ValidatedImage {
+    /// File extension the picture is stored under, e.g. `jpg` or `tiff`.
+    pub fn extension(&self) -> &'static str {
+        self.format.extensions_str()[0]
+    }
+}
+
+/// Sniff the magic bytes, check the dimensions against the configured limits
+/// and fully decode the image. Blocking.
+///
+/// `declared` is the client-supplied content type, if any; it has to agree
+/// with what the bytes actually are. `application/octet-stream` counts as
+/// not declared.
+pub fn validate(
+    path: &Path,
+    declared: Option<&mime::Mime>,
+) -> Result<ValidatedImage, ValidationError> {
+    let bytes = std::fs::read(path)?;
+    validate_bytes(
+        &bytes,
+        declared,
+        CONFIG.backend_max_image_width,
+        CONFIG.backend_max_image_height,
+    )
+}
+
+fn validate_bytes(
+    bytes: &[u8],
+    declared: Option<&mime::Mime>,
+    max_width: u32,
+    max_height: u32,
+) -> Result<ValidatedImage, ValidationError> {
+    let format = image::guess_format(bytes).map_err(|_| ValidationError::UnknownFormat)?;
+    let Some((_, content_types)) = ALLOWED_FORMATS.iter().find(|(f, _)| *f == format) else {
+        return Err(ValidationError::UnsupportedFormat(format));
+    };
+    if let Some(declared) = declared.filter(|m| **m != mime::APPLICATION_OCTET_STREAM)
+        && !content_types.contains(&declared.essence_str())
+    {
+        return Err(ValidationError::Mislabeled {
+            declared: declared.essence_str().to_owned(),
+            actual: format,
+        });
+    }
+
+    let reader = || ImageReader::with_format(Cursor::new(bytes), format);
+    let (width, height) = reader().into_dimensions().map_err(corrupt)?;
+    if width > max_width || height > max_height {
+        return Err(ValidationError::TooLarge {
+            width,
+            height,
+            max_width,
+            max_height,
+        });
+    }
+
+    // the JPEG decoder pads truncated scans instead of failing, so check for the EOI marker
+    if format == ImageFormat::Jpeg && !jpeg_is_complete(bytes) {
+        return Err(ValidationError::Corrupt("JPEG data is truncated".into()));
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(max_width);
+    limits.max_image_height = Some(max_height);
+    let mut full = reader();
+    full.limits(limits);
+    full.decode().map_err(corrupt)?;
+
+    Ok(ValidatedImage {
+        format,
+        width,
+        height,
+    })
+}
+
+/// Fully decode an image that is already stored, regardless of the upload
+/// limits. Blocking.
+pub fn check_decodable(path: &Path) -> Result<(), ValidationError> {
+    let bytes = std::fs::read(path)?;
+    let format = image::guess_format(&bytes).map_err(|_| ValidationError::UnknownFormat)?;
+    if format == ImageFormat::Jpeg && !jpeg_is_complete(&bytes) {
+        return Err(ValidationError::Corrupt("JPEG data is truncated".into()));
+    }
+    ImageReader::with_format(Cursor::new(&bytes), format)
+        .decode()
+        .map_err(corrupt)?;
+    Ok(())
+}
+
+fn corrupt(e: ImageError) -> ValidationError {
+    match e {
+        ImageError::Unsupported(e) => ValidationError::Corrupt(format!("unsupported feature: {e}")),
+        e => ValidationError::Corrupt(e.to_string()),
+    }
+}
+
+/// Walk the JPEG segments from the start-of-image marker to the end-of-image
+/// marker that closes the last scan. Anything after it, like the video of a
+/// motion photo, is ignored.
+fn jpeg_is_complete(bytes: &[u8]) -> bool {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return false;
+    }
+    let mut pos = 2;
+    loop {
+        // a marker is 0xFF, optionally padded with more 0xFF, then its code
+        if bytes.get(pos) != Some(&0xFF) {
+            return false;
+        }
+        while bytes.get(pos) == Some(&0xFF) {
+            pos += 1;
+        }
+        let Some(&marker) = bytes.get(pos) else {
+            return false;
+        };
+        pos += 1;
+        match marker {
+            0xD9 => return true,
+            // standalone markers without a length
+            0x01 | 0xD0..=0xD7 => continue,
+            _ => {}
+        }
+        let Some(len) = bytes.get(pos..pos + 2) else {
+            return false;
+        };
+        pos += u16::from_be_bytes([len[0], len[1]]) as usize;
+        if pos > bytes.len() {
+            return false;
+        }
+        if marker == 0xDA {
+            let Some(end) = scan_end(&bytes[pos..]) else {
+                return false;
+            };
+            pos += end;
+        }
+    }
+}
+
+/// Offset of the marker that ends an entropy-coded scan; stuffed `FF 00`
+/// bytes and restart markers are part of the scan.
+fn scan_end(data: &[u8]) -> Option<usize> {
+    data.windows(2)
+        .position(|w| w[0] == 0xFF && !matches!(w[1], 0x00 | 0xFF | 0xD0..=0xD7))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, RgbImage};
+
+    use super::*;
+
+    const MAX: u32 = 1000;
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let img = RgbImage::from_fn(64, 48, |x, y| image::Rgb([x as u8 * 4, y as u8 * 5, 128]));
+        let mut out = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut out, format)
+            .unwrap();
+        out.into_inner()
+    }
+
+    fn mime(s: &str) -> mime::Mime {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn accepts_every_allowed_format() {
+        for (format, content_types) in ALLOWED_FORMATS {
+            let bytes = encode(*format);
+            for declared in [None, Some(mime(content_types[0]))] {
+                let image = validate_bytes(&bytes, declared.as_ref(), MAX, MAX)
+                    .unwrap_or_else(|e| panic!("{format:?}: {e}"));
+                assert_eq!(image.format, *format);
+                assert_eq!((image.width, image.height), (64, 48));
+            }
+        }
+    }
+
+    #[test]
+    fn octet_stream_counts_as_undeclared() {
+        let bytes = encode(ImageFormat::Png);
+        let declared = mime::APPLICATION_OCTET_STREAM;
+        assert!(validate_bytes(&bytes, Some(&declared), MAX, MAX).is_ok());
+    }
+
+    #[test]
+    fn rejects_mislabeled_content() {
+        for (format, declared) in [
+            (ImageFormat::Png, "image/jpeg"),
+            (ImageFormat::Jpeg, "image/png"),
+            (ImageFormat::Gif, "image/webp"),
+            (ImageFormat::Bmp, "image/tiff"),
+        ] {
+            let bytes = encode(format);
+            let result = validate_bytes(&bytes, Some(&mime(declared)), MAX, MAX);
+            assert!(
+                matches!(result, Err(ValidationError::Mislabeled { actual, .. }) if actual == format),
+                "{format:?} declared as {declared}: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_images() {
+        for (format, _) in ALLOWED_FORMATS {
+            let bytes = encode(*format);
+            let truncated = &bytes[..bytes.len() * 2 / 3];
+            let result = validate_bytes(truncated, None, MAX, MAX);
+            assert!(
+                matches!(result, Err(ValidationError::Corrupt(_))),
+                "{format:?}: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_content() {
+        let result = validate_bytes(b"not an image at all", None, MAX, MAX);
+        assert!(matches!(result, Err(ValidationError::UnknownFormat)));
+    }
+
+    #[test]
+    fn rejects_images_over_the_limits() {
+        let bytes = encode(ImageFormat::Png);
+        let result = validate_bytes(&bytes, None, 32, MAX);
+        assert!(matches!(
+            result,
+            Err(ValidationError::TooLarge {
+                width: 64,
+                height: 48,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn accepts_data_after_the_end_of_a_jpeg() {
+        // motion photos append a video, which may contain any byte pairs
+        let mut bytes = encode(ImageFormat::Jpeg);
+        bytes.extend_from_slice(&[0x00, 0xFF, 0xDA, 0x00, 0x02, 0x17, 0xFF, 0xD8, 0x42]);
+        assert!(jpeg_is_complete(&bytes));
+        assert!(validate_bytes(&bytes, None, MAX, MAX).is_ok());
+    }
+
+    #[test]
+    fn jpeg_without_end_of_image_is_incomplete() {
+        let bytes = encode(ImageFormat::Jpeg);
+        assert!(jpeg_is_complete(&bytes));
+        assert!(!jpeg_is_complete(&bytes[..bytes.len() - 2]));
+        assert!(!jpeg_is_complete(&bytes[..bytes.len() / 2]));
+    }
+}
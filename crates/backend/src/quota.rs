@@ -0,0 +1,209 @@
+//! Limits on how much the library may hold. New pictures are checked against
+//! them just before they are stored; if configured, the trash is emptied and
+//! the oldest pictures that are neither pinned nor favorites are deleted to
+//! make room.
+
+use std::path::Path;
+
+use anyhow::Context;
+use tokio::sync::{Mutex, MutexGuard};
+
+use libs::frame_settings::SharedSettings;
+
+use crate::{
+    CONFIG,
+    common::{ApiError, ApiResult, metrics},
+    db::{Picture, Repository},
+    media::{
+        derivatives::{self, DerivativeSize},
+        normalize,
+    },
+    trash,
+};
+
+/// Held from the quota check until the new picture is stored, so concurrent
+/// additions cannot both take the last free space.
+static ADDING: Mutex<()> = Mutex::const_new(());
+
+/// Room for one more picture, reserved until dropped. Holds nothing when no
+/// quota is set.
+pub struct Reservation {
+    _guard: Option<MutexGuard<'static, ()>>,
+}
+
+fn quotas_set() -> bool {
+    CONFIG.backend_quota_bytes.is_some() || CONFIG.backend_quota_pictures.is_some()
+}
+
+async fn data_dir_size() -> ApiResult<u64> {
+    let data_dir = CONFIG.backend_data_dir.clone();
+    tokio::task::spawn_blocking(move || metrics::folder_size(&data_dir))
+        .await
+        .map_err(|e| ApiError::Internal(e.into()))
+}
+
+/// What the library holds now, in pictures and bytes.
+struct Usage {
+    pictures: u64,
+    bytes: u64,
+}
+
+impl Usage {
+    /// Leaves out `staged` bytes in the data dir that are about to be stored
+    /// or dropped.
+    async fn measure(repo: &Repository, staged: u64) -> ApiResult<Self> {
+        Ok(Usage {
+            pictures: repo.count_pictures().await? as u64,
+            bytes: data_dir_size().await?.saturating_sub(staged),
+        })
+    }
+
+    /// Why one more picture of `incoming` bytes does not fit, if it does not.
+    fn overrun(&self, incoming: u64) -> Option<String> {
+        if let Some(max) = CONFIG.backend_quota_pictures
+            && self.pictures >= max
+        {
+            return Some(format!("the library is limited to {max} pictures"));
+        }
+        if let Some(max) = CONFIG.backend_quota_bytes
+            && self.bytes.saturating_add(incoming) > max
+        {
+            return Some(format!(
+                "the library is limited to {max} bytes and {} are in use",
+                self.bytes
+            ));
+        }
+        None
+    }
+}
+
+/// A single picture larger than the whole quota; evicting would not help.
+fn too_large(size: u64) -> ApiResult<()> {
+    match CONFIG.backend_quota_bytes {
+        Some(max) if size > max => Err(ApiError::InsufficientStorage(format!(
+            "the library is limited to {max} bytes"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Reject up front what [`reserve`] is bound to reject, e.g. before a
+/// resumable upload of `incoming` bytes starts.
+pub async fn precheck(repo: &Repository, incoming: u64) -> ApiResult<()> {
+    if !quotas_set() {
+        return Ok(());
+    }
+    too_large(incoming)?;
+    if CONFIG.backend_quota_evict {
+        return Ok(());
+    }
+    match Usage::measure(repo, 0).await?.overrun(incoming) {
+        Some(reason) => Err(ApiError::InsufficientStorage(reason)),
+        None => Ok(()),
+    }
+}
+
+/// Make sure a new picture that stores `size` bytes, kept original included,
+/// fits within the quotas. Until then its files take `staged` bytes of the
+/// data dir, which are not counted twice. The trash and old pictures are
+/// deleted first if configured to.
+pub async fn reserve(
+    repo: &Repository,
+    settings: &SharedSettings,
+    size: u64,
+    staged: u64,
+) -> ApiResult<Reservation> {
+    if !quotas_set() {
+        return Ok(Reservation { _guard: None });
+    }
+    let reservation = Reservation {
+        _guard: Some(ADDING.lock().await),
+    };
+    too_large(size)?;
+
+    let Some(reason) = Usage::measure(repo, staged).await?.overrun(size) else {
+        return Ok(reservation);
+    };
+    if !CONFIG.backend_quota_evict {
+        return Err(ApiError::InsufficientStorage(reason));
+    }
+
+    let trashed = repo.list_trashed_before(i64::MAX).await?;
+    if !trashed.is_empty() {
+        let n = trash::purge(repo, &trashed)
+            .await
+            .context("emptying the trash")?;
+        tracing::info!("emptied {n} pictures from the trash to make room: {reason}");
+    }
+    let usage = Usage::measure(repo, staged).await?;
+    if usage.overrun(size).is_none() {
+        return Ok(reservation);
+    }
+
+    let pinned = settings.get().await.pinned_image;
+    let candidates = repo.list_evictable_pictures(pinned).await?;
+    let victims = victims(&usage, size, candidates).await?;
+    for picture in victims {
+        evict(repo, settings, &picture.id).await?;
+        tracing::info!(
+            "evicted picture {} ({}) to make room",
+            picture.id,
+            picture.filename
+        );
+    }
+    Ok(reservation)
+}
+
+/// The oldest of `candidates` that have to go for `usage` and `incoming`
+/// more bytes to fit within the quotas. Fails without evicting anything if
+/// even all of them would not be enough.
+async fn victims(
+    usage: &Usage,
+    incoming: u64,
+    candidates: Vec<Picture>,
+) -> ApiResult<Vec<Picture>> {
+    let (mut pictures, mut bytes) = (usage.pictures, usage.bytes);
+    let mut victims = Vec::new();
+    for picture in candidates {
+        if (Usage { pictures, bytes }).overrun(incoming).is_none() {
+            return Ok(victims);
+        }
+        let filename = picture.filename.clone();
+        let freed = tokio::task::spawn_blocking(move || footprint(&filename))
+            .await
+            .map_err(|e| ApiError::Internal(e.into()))?;
+        pictures -= 1;
+        bytes = bytes.saturating_sub(freed);
+        victims.push(picture);
+    }
+    match (Usage { pictures, bytes }).overrun(incoming) {
+        None => Ok(victims),
+        Some(reason) => Err(ApiError::InsufficientStorage(format!(
+            "{reason}, even after deleting every picture that is neither pinned nor a favorite"
+        ))),
+    }
+}
+
+/// Bytes deleting a picture frees: its file, derivatives and kept original.
+/// Blocking.
+fn footprint(filename: &str) -> u64 {
+    let size = |path: &Path| std::fs::metadata(path).map_or(0, |m| m.len());
+    let file = size(&Path::new(&CONFIG.backend_data_dir).join(filename));
+    let derived: u64 = DerivativeSize::ALL
+        .into_iter()
+        .map(|s| size(&derivatives::derivative_path(filename, s)))
+        .sum();
+    let original = normalize::find_original(filename).map_or(0, |p| size(&p));
+    file + derived + original
+}
+
+/// Delete a picture for good; the trash would not free any space.
+async fn evict(repo: &Repository, settings: &SharedSettings, id: &str) -> ApiResult<()> {
+    if trash::trash_picture(repo, settings, id).await?.is_none() {
+        return Ok(());
+    }
+    trash::purge(repo, &[id.to_owned()])
+        .await
+        .context("purging an evicted picture")?;
+    Ok(())
+}
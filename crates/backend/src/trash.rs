@@ -0,0 +1,178 @@
+//! Deleted pictures are kept in the trash for a while before they are purged.
+//! Their files move to a hidden sub-folder so the display drops them.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+
+use libs::frame_settings::SharedSettings;
+
+use crate::{
+    CONFIG,
+    db::Repository,
+    library,
+    media::{derivatives, normalize},
+};
+
+/// Hidden sub-folder of the data dir holding the files of trashed pictures.
+pub const TRASH_DIR: &str = ".trash";
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+fn live_path(filename: &str) -> PathBuf {
+    Path::new(&CONFIG.backend_data_dir).join(filename)
+}
+
+fn trash_path(filename: &str) -> PathBuf {
+    Path::new(&CONFIG.backend_data_dir)
+        .join(TRASH_DIR)
+        .join(filename)
+}
+
+/// Move a picture to the trash, file included, and unpin it if it was
+/// pinned. Returns its filename; `None` if there is no such picture outside
+/// the trash. If the file cannot be moved, the picture stays where it was.
+pub async fn trash_picture(
+    repo: &Repository,
+    settings: &SharedSettings,
+    id: &str,
+) -> Result<Option<String>> {
+    let mut outcomes = trash_pictures(repo, settings, vec![id.to_owned()]).await?;
+    outcomes.pop().expect("one outcome per id")
+}
+
+/// [`trash_picture`] for many pictures at once. The rows are all trashed in
+/// one transaction, the files are moved once it has committed. Yields an
+/// outcome per id, in order.
+pub async fn trash_pictures(
+    repo: &Repository,
+    settings: &SharedSettings,
+    ids: Vec<String>,
+) -> Result<Vec<Result<Option<String>>>> {
+    let trashed = repo.trash_pictures(ids.clone()).await?;
+    let pinned = settings.get().await.pinned_image;
+    let mut unpin = false;
+
+    let mut outcomes = Vec::with_capacity(ids.len());
+    for (id, filename) in ids.iter().zip(trashed) {
+        let Some(filename) = filename else {
+            outcomes.push(Ok(None));
+            continue;
+        };
+        if let Err(e) = move_to_trash(&filename).await {
+            repo.restore_picture(id).await?;
+            outcomes.push(Err(e));
+            continue;
+        }
+        unpin |= pinned.as_ref() == Some(&filename);
+        outcomes.push(Ok(Some(filename)));
+    }
+    // hidden from albums, tags and captions now
+    library::changed();
+
+    if unpin {
+        settings
+            .update(|s| {
+                s.pinned_image = None;
+            })
+            .await?;
+    }
+    Ok(outcomes)
+}
+
+/// Move a picture's file into the trash folder. A file that is already gone
+/// is not an error.
+pub async fn move_to_trash(filename: &str) -> Result<()> {
+    let (from, to) = (live_path(filename), trash_path(filename));
+    tokio::fs::create_dir_all(to.parent().expect("trash path has a parent")).await?;
+    match tokio::fs::rename(&from, &to).await {
+        Ok(()) => {
+            tracing::debug!("moved {} to the trash", from.display());
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            tracing::warn!("file already gone: {}", from.display());
+            Ok(())
+        }
+        Err(e) => Err(e).with_context(|| format!("cannot move {from:?} to {to:?}")),
+    }
+}
+
+/// Bring a picture back from the trash, file included. Returns `false` if it
+/// is not in the trash. If the file cannot be moved back, the picture stays
+/// in the trash.
+pub async fn restore_picture(repo: &Repository, id: &str) -> Result<bool> {
+    let Some(picture) = repo.get_trashed_picture(id).await? else {
+        return Ok(false);
+    };
+    // the row first, so a purge running meanwhile cannot strand the file
+    if !repo.restore_picture(id).await? {
+        return Ok(false);
+    }
+    if let Err(e) = restore_from_trash(&picture.filename).await {
+        repo.trash_picture(id).await?;
+        return Err(e);
+    }
+    library::changed();
+    Ok(true)
+}
+
+/// Move a picture's file back out of the trash folder.
+async fn restore_from_trash(filename: &str) -> Result<()> {
+    let (from, to) = (trash_path(filename), live_path(filename));
+    tokio::fs::rename(&from, &to)
+        .await
+        .with_context(|| format!("cannot move {from:?} to {to:?}"))
+}
+
+/// Delete trashed pictures for good, files included. Returns how many were
+/// purged; ids no longer in the trash are skipped.
+pub async fn purge(repo: &Repository, ids: &[String]) -> Result<usize> {
+    let mut purged = 0;
+    for id in ids {
+        let Some(filename) = repo.purge_picture(id).await? else {
+            continue;
+        };
+        let path = trash_path(&filename);
+        match tokio::fs::remove_file(&path).await {
+            Ok(_) => tracing::debug!("removed file {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::warn!("file already gone: {}", path.display())
+            }
+            // the row is already gone; the file is left behind
+            Err(e) => tracing::error!("failed to delete {path:?}: {e}"),
+        }
+        derivatives::remove(&filename).await;
+        normalize::remove_original(&filename).await;
+        purged += 1;
+    }
+    Ok(purged)
+}
+
+/// Spawn a background job that purges pictures trashed longer ago than the
+/// configured retention period.
+pub fn spawn_purge(repo: Arc<Repository>) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            tick.tick().await;
+            if let Err(e) = purge_expired(&repo).await {
+                tracing::error!("trash purge failed: {e:#}");
+            }
+        }
+    });
+}
+
+async fn purge_expired(repo: &Repository) -> Result<()> {
+    let retention = Duration::from_secs(CONFIG.backend_trash_retention_days * 24 * 60 * 60);
+    let cutoff = chrono::Utc::now().timestamp_millis() - retention.as_millis() as i64;
+    let expired = repo.list_trashed_before(cutoff).await?;
+    if !expired.is_empty() {
+        let n = purge(repo, &expired).await?;
+        tracing::info!("purged {n} pictures from the trash");
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Temp files and housekeeping for resumable uploads.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use once_cell::sync::Lazy;
+
+use crate::{CONFIG, db::Repository};
+
+/// Hidden sub-folder of the data dir holding the partial files of open sessions.
+const UPLOADS_DIR: &str = ".uploads";
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Sessions with a request currently writing to or finalizing them.
+static BUSY: Lazy<Mutex<HashSet<String>>> = Lazy::new(Default::default);
+
+/// Marks a session as busy until dropped.
+pub struct SessionGuard(String);
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        BUSY.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Claim a session for the current request; `None` if another request holds it.
+pub fn try_claim(id: &str) -> Option<SessionGuard> {
+    BUSY.lock()
+        .unwrap()
+        .insert(id.to_owned())
+        .then(|| SessionGuard(id.to_owned()))
+}
+
+pub fn part_path(id: &str) -> PathBuf {
+    Path::new(&CONFIG.backend_data_dir)
+        .join(UPLOADS_DIR)
+        .join(format!("{id}.part"))
+}
+
+pub async fn create_part_file(id: &str) -> std::io::Result<()> {
+    let path = part_path(id);
+    tokio::fs::create_dir_all(path.parent().expect("part path has a parent")).await?;
+    tokio::fs::File::create(&path).await?;
+    Ok(())
+}
+
+pub async fn remove_part_file(id: &str) {
+    let path = part_path(id);
+    match tokio::fs::remove_file(&path).await {
+        Ok(_) => tracing::debug!("removed {}", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => tracing::error!("failed to delete {path:?}: {e}"),
+    }
+}
+
+/// Spawn a background job that drops sessions idle for longer than the
+/// configured TTL, together with their partial files.
+pub fn spawn_expiry(repo: Arc<Repository>) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(EXPIRY_INTERVAL);
+        loop {
+            tick.tick().await;
+            if let Err(e) = expire(&repo).await {
+                tracing::error!("upload session expiry failed: {e:#}");
+            }
+        }
+    });
+}
+
+async fn expire(repo: &Repository) -> anyhow::Result<()> {
+    let ttl = Duration::from_secs(CONFIG.backend_upload_session_ttl_secs);
+    let cutoff = chrono::Utc::now().timestamp_millis() - ttl.as_millis() as i64;
+
+    for id in repo.list_stale_upload_sessions(cutoff).await? {
+        // a request is writing to it right now
+        let Some(_guard) = try_claim(&id) else {
+            continue;
+        };
+        if repo.delete_stale_upload_session(&id, cutoff).await? {
+            tracing::info!("upload session {id} expired");
+            remove_part_file(&id).await;
+        }
+    }
+
+    // partial files whose session row is gone, e.g. after a crash
+    let live: HashSet<String> = repo.list_upload_session_ids().await?.into_iter().collect();
+    let dir = Path::new(&CONFIG.backend_data_dir).join(UPLOADS_DIR);
+    let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+        return Ok(());
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let stale = entry
+            .metadata()
+            .await?
+            .modified()
+            .ok()
+            .and_then(|m| SystemTime::now().duration_since(m).ok())
+            .is_some_and(|age| age > ttl);
+        if !live.contains(id) && stale {
+            remove_part_file(id).await;
+        }
+    }
+    Ok(())
+}
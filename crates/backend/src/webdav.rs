@@ -0,0 +1,539 @@
+//! Optional WebDAV listener that exposes the library as a single flat folder,
+//! so it can be mounted in a file manager. Files are listed under their stored
+//! names; a file written to the folder goes through the same checks as an
+//! upload and shows up under a fresh name, and deleting one moves the picture
+//! to the trash.
+//!
+//! Clients log in with any user name and an API key as the password; `ro`
+//! keys can only browse and download.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+use axum::{
+    Router,
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use bytes::{Buf, Bytes};
+use dav_server::{
+    DavConfig, DavHandler, DavMethodSet,
+    davpath::DavPath,
+    fakels::FakeLs,
+    fs::{
+        DavDirEntry, DavFile, DavFileSystem, DavMetaData, FsError, FsFuture, FsResult, FsStream,
+        OpenOptions, ReadDirMeta,
+    },
+};
+use futures::{FutureExt, stream};
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use tokio::{
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+use crate::{
+    CONFIG,
+    common::{ApiError, AppState},
+    db::{Picture, PictureFilter, PictureSort},
+    ingest::{self, Ingested},
+    trash,
+};
+
+const REALM: &str = r#"Basic realm="picture_frame""#;
+/// How long a verified key is trusted without checking it again; a revoked
+/// key keeps working over WebDAV for at most this long.
+const AUTH_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Scope of a verified key and when it was verified, by the key's SHA-256.
+type VerifiedKeys = HashMap<[u8; 32], (String, Instant)>;
+
+/// File managers send bursts of requests and every argon2 check is slow.
+static VERIFIED: Lazy<Mutex<VerifiedKeys>> = Lazy::new(Default::default);
+
+#[derive(Clone)]
+struct Dav {
+    state: AppState,
+    handler: DavHandler,
+}
+
+/// Serve the library over WebDAV until `shutdown` completes, if a port is
+/// configured.
+pub async fn serve(
+    state: AppState,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let Some(port) = CONFIG.backend_webdav_port else {
+        return Ok(());
+    };
+    let listener = TcpListener::bind(format!("{}:{port}", CONFIG.backend_ipv4_address)).await?;
+    tracing::info!("⇢ WebDAV listening on: http://{}", listener.local_addr()?);
+
+    let handler = DavHandler::builder()
+        .filesystem(Box::new(LibraryFs {
+            state: state.clone(),
+        }))
+        // Finder and Explorer only write to shares that support locking
+        .locksystem(FakeLs::new())
+        .build_handler();
+    let router = Router::new()
+        .fallback(handle)
+        .with_state(Dav { state, handler });
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+async fn handle(State(dav): State<Dav>, req: Request) -> Response {
+    let scope = match authorize(&dav.state, req.headers()).await {
+        Ok(scope) => scope,
+        Err(StatusCode::UNAUTHORIZED) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, HeaderValue::from_static(REALM))],
+            )
+                .into_response();
+        }
+        Err(status) => return status.into_response(),
+    };
+
+    let response = if scope == "rw" {
+        dav.handler.handle(req).await
+    } else {
+        let read_only = DavConfig::new().methods(DavMethodSet::WEBDAV_RO);
+        dav.handler.handle_with(read_only, req).await
+    };
+    response.map(Body::new)
+}
+
+/// Scope of the API key sent as the Basic auth password, or as a bearer
+/// token like for the API.
+async fn authorize(state: &AppState, headers: &HeaderMap) -> Result<String, StatusCode> {
+    let auth = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let token = if let Some(token) = auth.strip_prefix("Bearer ") {
+        token.trim().to_owned()
+    } else if let Some(basic) = auth.strip_prefix("Basic ") {
+        let decoded = BASE64_STANDARD
+            .decode(basic.trim())
+            .ok()
+            .and_then(|d| String::from_utf8(d).ok())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        let (_user, password) = decoded.split_once(':').ok_or(StatusCode::UNAUTHORIZED)?;
+        password.to_owned()
+    } else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    let digest: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+    if let Some((scope, at)) = VERIFIED.lock().unwrap().get(&digest)
+        && at.elapsed() < AUTH_CACHE_TTL
+    {
+        return Ok(scope.clone());
+    }
+
+    match state.repo.verify_api_key(&token).await {
+        Ok(Some((_, scope))) => {
+            let mut verified = VERIFIED.lock().unwrap();
+            verified.retain(|_, (_, at)| at.elapsed() < AUTH_CACHE_TTL);
+            verified.insert(digest, (scope.clone(), Instant::now()));
+            Ok(scope)
+        }
+        Ok(None) => Err(StatusCode::UNAUTHORIZED),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+fn internal(err: impl fmt::Display) -> FsError {
+    tracing::error!("webdav: {err:#}");
+    FsError::GeneralFailure
+}
+
+/// File name `path` points to, or `None` for the root folder. There are no
+/// sub-folders.
+fn file_name(path: &DavPath) -> FsResult<Option<&str>> {
+    let name = std::str::from_utf8(path.as_bytes())
+        .map_err(|_| FsError::NotFound)?
+        .trim_matches('/');
+    match name {
+        "" => Ok(None),
+        name if name.contains('/') => Err(FsError::NotFound),
+        name => Ok(Some(name)),
+    }
+}
+
+fn stored_path(filename: &str) -> PathBuf {
+    Path::new(&CONFIG.backend_data_dir).join(filename)
+}
+
+#[derive(Debug, Clone)]
+struct Meta {
+    len: u64,
+    modified: SystemTime,
+    is_dir: bool,
+}
+
+impl Meta {
+    async fn of(path: &Path) -> FsResult<Meta> {
+        let meta = tokio::fs::metadata(path).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                FsError::NotFound
+            } else {
+                internal(e)
+            }
+        })?;
+        Ok(Meta {
+            len: meta.len(),
+            modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            is_dir: meta.is_dir(),
+        })
+    }
+}
+
+impl DavMetaData for Meta {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn modified(&self) -> FsResult<SystemTime> {
+        Ok(self.modified)
+    }
+
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+}
+
+struct Entry {
+    name: String,
+    meta: Meta,
+}
+
+impl DavDirEntry for Entry {
+    fn name(&self) -> Vec<u8> {
+        self.name.clone().into_bytes()
+    }
+
+    fn metadata(&self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        let meta = self.meta.clone();
+        async move { Ok(Box::new(meta) as Box<dyn DavMetaData>) }.boxed()
+    }
+}
+
+/// The live pictures of the library, backed by the repository.
+#[derive(Clone)]
+struct LibraryFs {
+    state: AppState,
+}
+
+impl LibraryFs {
+    async fn picture(&self, filename: &str) -> FsResult<Picture> {
+        self.state
+            .repo
+            .find_picture_by_filename(filename)
+            .await
+            .map_err(internal)?
+            .ok_or(FsError::NotFound)
+    }
+
+    async fn create(&self, name: &str) -> FsResult<Box<dyn DavFile>> {
+        // e.g. `.DS_Store` and the `._` files macOS writes alongside
+        if name.starts_with('.') {
+            return Err(FsError::Forbidden);
+        }
+        let (staged, file) = ingest::create_staging_file().await.map_err(internal)?;
+        Ok(Box::new(Upload {
+            state: self.state.clone(),
+            staged,
+            file,
+            written: 0,
+            stored: None,
+        }))
+    }
+
+    /// Same as `DELETE /api/pictures/{id}`.
+    async fn delete(&self, filename: &str) -> FsResult<()> {
+        let picture = self.picture(filename).await?;
+        let trashed = trash::trash_picture(&self.state.repo, &self.state.settings, &picture.id)
+            .await
+            .map_err(internal)?;
+        if trashed.is_none() {
+            return Err(FsError::NotFound);
+        }
+        tracing::info!("deleted picture {} over WebDAV", picture.id);
+        Ok(())
+    }
+}
+
+impl DavFileSystem for LibraryFs {
+    fn open<'a>(
+        &'a self,
+        path: &'a DavPath,
+        options: OpenOptions,
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        async move {
+            let Some(name) = file_name(path)? else {
+                return Err(FsError::Forbidden);
+            };
+            let existing = self.picture(name).await;
+            if options.write {
+                // stored pictures are never changed in place
+                return match existing {
+                    Ok(_) => Err(FsError::Forbidden),
+                    Err(FsError::NotFound) if options.create || options.create_new => {
+                        self.create(name).await
+                    }
+                    Err(e) => Err(e),
+                };
+            }
+            let path = stored_path(&existing?.filename);
+            let meta = Meta::of(&path).await?;
+            let file = tokio::fs::File::open(&path).await.map_err(internal)?;
+            Ok(Box::new(Download { file, meta }) as Box<dyn DavFile>)
+        }
+        .boxed()
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a DavPath,
+        _meta: ReadDirMeta,
+    ) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+        async move {
+            if file_name(path)?.is_some() {
+                return Err(FsError::NotFound);
+            }
+            let pictures = self
+                .state
+                .repo
+                .list_pictures(
+                    &PictureFilter::default(),
+                    PictureSort::default(),
+                    None,
+                    None,
+                )
+                .await
+                .map_err(internal)?;
+            let mut entries = Vec::with_capacity(pictures.len());
+            for picture in pictures {
+                match Meta::of(&stored_path(&picture.filename)).await {
+                    Ok(meta) => entries.push(Ok(Box::new(Entry {
+                        name: picture.filename,
+                        meta,
+                    }) as Box<dyn DavDirEntry>)),
+                    Err(_) => tracing::warn!("file of picture {} is missing", picture.id),
+                }
+            }
+            Ok(Box::pin(stream::iter(entries)) as FsStream<Box<dyn DavDirEntry>>)
+        }
+        .boxed()
+    }
+
+    fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        async move {
+            let meta = match file_name(path)? {
+                None => Meta::of(Path::new(&CONFIG.backend_data_dir)).await?,
+                Some(name) => {
+                    let picture = self.picture(name).await?;
+                    Meta::of(&stored_path(&picture.filename)).await?
+                }
+            };
+            Ok(Box::new(meta) as Box<dyn DavMetaData>)
+        }
+        .boxed()
+    }
+
+    fn remove_file<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            match file_name(path)? {
+                Some(name) => self.delete(name).await,
+                None => Err(FsError::Forbidden),
+            }
+        }
+        .boxed()
+    }
+
+    fn create_dir<'a>(&'a self, _path: &'a DavPath) -> FsFuture<'a, ()> {
+        async { Err(FsError::Forbidden) }.boxed()
+    }
+}
+
+/// A stored picture opened for reading.
+#[derive(Debug)]
+struct Download {
+    file: tokio::fs::File,
+    meta: Meta,
+}
+
+impl DavFile for Download {
+    fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        let meta = self.meta.clone();
+        async move { Ok(Box::new(meta) as Box<dyn DavMetaData>) }.boxed()
+    }
+
+    fn write_buf(&mut self, _buf: Box<dyn Buf + Send>) -> FsFuture<'_, ()> {
+        async { Err(FsError::Forbidden) }.boxed()
+    }
+
+    fn write_bytes(&mut self, _buf: Bytes) -> FsFuture<'_, ()> {
+        async { Err(FsError::Forbidden) }.boxed()
+    }
+
+    fn read_bytes(&mut self, count: usize) -> FsFuture<'_, Bytes> {
+        async move {
+            let mut buf = vec![0; count];
+            let n = self.file.read(&mut buf).await.map_err(internal)?;
+            buf.truncate(n);
+            Ok(Bytes::from(buf))
+        }
+        .boxed()
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> FsFuture<'_, u64> {
+        async move { self.file.seek(pos).await.map_err(internal) }.boxed()
+    }
+
+    fn flush(&mut self) -> FsFuture<'_, ()> {
+        async { Ok(()) }.boxed()
+    }
+}
+
+/// A new file being written. It is staged like an upload and registered once
+/// the client has sent all of it.
+struct Upload {
+    state: AppState,
+    staged: PathBuf,
+    file: tokio::fs::File,
+    written: u64,
+    /// Set once registered, to the picture the content ended up in.
+    stored: Option<Meta>,
+}
+
+impl fmt::Debug for Upload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upload")
+            .field("staged", &self.staged)
+            .field("written", &self.written)
+            .finish()
+    }
+}
+
+impl Upload {
+    async fn write(&mut self, chunk: &[u8]) -> FsResult<()> {
+        if self.stored.is_some() {
+            return Err(FsError::Forbidden);
+        }
+        self.written += chunk.len() as u64;
+        if self.written > CONFIG.backend_max_upload_bytes as u64 {
+            return Err(FsError::TooLarge);
+        }
+        self.file.write_all(chunk).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::StorageFull {
+                FsError::InsufficientStorage
+            } else {
+                internal(e)
+            }
+        })
+    }
+}
+
+impl DavFile for Upload {
+    fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        async move {
+            let meta = match &self.stored {
+                Some(meta) => meta.clone(),
+                None => Meta::of(&self.staged).await?,
+            };
+            Ok(Box::new(meta) as Box<dyn DavMetaData>)
+        }
+        .boxed()
+    }
+
+    fn write_buf(&mut self, mut buf: Box<dyn Buf + Send>) -> FsFuture<'_, ()> {
+        async move {
+            while buf.has_remaining() {
+                let chunk = buf.chunk().to_vec();
+                self.write(&chunk).await?;
+                buf.advance(chunk.len());
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn write_bytes(&mut self, buf: Bytes) -> FsFuture<'_, ()> {
+        async move { self.write(&buf).await }.boxed()
+    }
+
+    fn read_bytes(&mut self, _count: usize) -> FsFuture<'_, Bytes> {
+        async { Err(FsError::Forbidden) }.boxed()
+    }
+
+    fn seek(&mut self, _pos: SeekFrom) -> FsFuture<'_, u64> {
+        async { Err(FsError::NotImplemented) }.boxed()
+    }
+
+    fn flush(&mut self) -> FsFuture<'_, ()> {
+        async move {
+            if self.stored.is_some() {
+                return Ok(());
+            }
+            self.file.sync_all().await.map_err(internal)?;
+            let picture = match ingest::register(
+                &self.state.repo,
+                &self.state.settings,
+                &self.staged,
+                None,
+                None,
+                false,
+            )
+            .await
+            {
+                Ok(Ingested::Created(picture)) => {
+                    tracing::info!("stored {} over WebDAV", picture.filename);
+                    picture
+                }
+                // nothing new to store; the client sees its write succeed
+                Ok(Ingested::Duplicate(existing)) => existing,
+                Err(ApiError::PayloadTooLarge(_)) => return Err(FsError::TooLarge),
+                Err(ApiError::InsufficientStorage(reason)) => {
+                    tracing::warn!("rejected a WebDAV upload: {reason}");
+                    return Err(FsError::InsufficientStorage);
+                }
+                Err(ApiError::Internal(e)) => return Err(internal(e)),
+                Err(e) => {
+                    tracing::warn!("rejected a WebDAV upload: {e}");
+                    return Err(FsError::Forbidden);
+                }
+            };
+            self.stored = Some(Meta::of(&stored_path(&picture.filename)).await?);
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        // registering consumes the staged file, otherwise the client gave up
+        if self.stored.is_none()
+            && let Err(e) = std::fs::remove_file(&self.staged)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::error!("failed to remove staged file {:?}: {e}", self.staged);
+        }
+    }
+}
@@ -7,6 +7,20 @@ use libs::util;
 pub struct Config {
     pub backend_data_dir: String,
     pub backend_frame_settings_file: String,
+    /// Albums and other library data published by the backend for the display.
+    #[serde(default = "default_library_index_file")]
+    pub backend_library_index_file: String,
+    /// TrueType font used for captions.
+    #[serde(default = "default_caption_font")]
+    pub display_caption_font: String,
+}
+
+fn default_library_index_file() -> String {
+    "library.toml".into()
+}
+
+fn default_caption_font() -> String {
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf".into()
 }
 
 pub static CONFIG: Lazy<Config> = Lazy::new(|| {
@@ -23,12 +37,17 @@ pub static CONFIG: Lazy<Config> = Lazy::new(|| {
         .join(&config.backend_frame_settings_file)
         .to_string_lossy()
         .into_owned();
+    let backend_library_index_file = config_dir
+        .join(&config.backend_library_index_file)
+        .to_string_lossy()
+        .into_owned();
 
     std::fs::create_dir_all(&backend_data_dir).expect("Failed to create data directory");
 
     // update the config with the full paths
     config.backend_data_dir = backend_data_dir;
     config.backend_frame_settings_file = backend_frame_settings_file;
+    config.backend_library_index_file = backend_library_index_file;
 
     config
 });
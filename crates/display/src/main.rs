@@ -8,51 +8,123 @@ use std::{
 };
 
 use anyhow::{Context, Result};
+use chrono::{Local, NaiveDate};
 use exif::{In, Reader as ExifReader, Tag};
 use image::{GenericImageView, imageops};
 use notify::{
     RecommendedWatcher, RecursiveMode, Watcher,
     event::{CreateKind, EventKind, ModifyKind, RemoveKind},
 };
-use rand::seq::SliceRandom;
+use rand::{Rng, seq::SliceRandom};
 use sdl2::{
     event::Event,
     keyboard::Keycode,
     pixels::{Color, PixelFormatEnum},
     rect::Rect,
+    render::BlendMode,
+    ttf::{Font, Sdl2TtfContext},
 };
 use tokio::{sync::Notify, time::Instant};
 use tracing_subscriber::EnvFilter;
 
 use libs::{
-    frame_settings::{FrameSettings, SharedSettings},
+    frame_settings::{CaptionPosition, FitMode, FrameSettings, SharedSettings, ShuffleMode},
+    framing::{Crop, FocalPoint, Rotation},
+    library_index::LibraryIndex,
     util,
 };
 
 use config::CONFIG;
 
-/// Collect all *.jpg / *.png files in a directory (non‑recursive).
+/// Collect all supported image files in a directory (non‑recursive).
 fn scan_images(dir: &Path) -> Vec<PathBuf> {
     let mut files: Vec<_> = fs::read_dir(dir)
         .unwrap_or_else(|_| panic!("cannot read {:?}", dir))
         .filter_map(|e| e.ok())
         .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
         .map(|e| e.path())
-        .filter(|p| {
-            matches!(
-                p.extension()
-                    .and_then(|e| e.to_str())
-                    .unwrap_or("")
-                    .to_ascii_lowercase()
-                    .as_str(),
-                "jpg" | "png"
-            )
-        })
+        .filter(|p| util::is_image_path(p))
         .collect();
     files.sort();
     files
 }
 
+/// Read the library index; a missing or unreadable index counts as empty.
+fn load_library_index(path: &Path) -> LibraryIndex {
+    LibraryIndex::load(path).unwrap_or_else(|e| {
+        tracing::warn!("cannot read library index {path:?}: {e}");
+        LibraryIndex::default()
+    })
+}
+
+/// The images in rotation: everything in `dir`, or only the members of the
+/// active albums, narrowed down by the tag filter and the pictures' schedules
+/// for `today`. Shuffled if configured.
+fn collect_images(
+    dir: &Path,
+    settings: &FrameSettings,
+    library: &LibraryIndex,
+    today: NaiveDate,
+) -> Vec<PathBuf> {
+    let mut images = scan_images(dir);
+    if !settings.active_albums.is_empty() {
+        match library.album_members(&settings.active_albums) {
+            Some(members) => images.retain(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| members.contains(n))
+            }),
+            None => tracing::warn!(
+                albums = ?settings.active_albums,
+                "active albums missing from library index, showing all images"
+            ),
+        }
+    }
+    if let Some(filter) = &settings.tag_filter {
+        images.retain(|p| {
+            let name = p.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            filter.matches(library.tags(name))
+        });
+    }
+    images.retain(|p| {
+        let name = p.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        library.is_scheduled(name, today)
+    });
+    if settings.shuffle {
+        match settings.shuffle_mode {
+            ShuffleMode::Uniform => images.shuffle(&mut rand::rng()),
+            ShuffleMode::Weighted => {
+                images = weighted_shuffle(images, library, settings.favorite_weight)
+            }
+        }
+    }
+    images
+}
+
+/// Shuffle in which every image appears as many times per round as its
+/// weight, its appearances spread out evenly over the round.
+fn weighted_shuffle(
+    images: Vec<PathBuf>,
+    library: &LibraryIndex,
+    favorite_weight: u32,
+) -> Vec<PathBuf> {
+    let mut rng = rand::rng();
+    let mut slots = Vec::with_capacity(images.len());
+    for path in images {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let weight = library.weight(name, favorite_weight);
+        let offset: f64 = rng.random();
+        for k in 0..weight {
+            slots.push(((k as f64 + offset) / weight as f64, path.clone()));
+        }
+    }
+    slots.sort_by(|a, b| a.0.total_cmp(&b.0));
+    slots.into_iter().map(|(_, path)| path).collect()
+}
+
 /// Find the index of a pinned image in the list, if it exists.
 fn find_pinned_image_index(images: &[PathBuf], pinned_filename: &str) -> Option<usize> {
     images.iter().position(|p| {
@@ -63,11 +135,125 @@ fn find_pinned_image_index(images: &[PathBuf], pinned_filename: &str) -> Option<
     })
 }
 
-/// Load an image and blit it full‑screen (keep aspect).
+/// How a picture is cut and scaled to the window.
+struct Framing {
+    rotation: Rotation,
+    crop: Option<Crop>,
+    focal_point: Option<FocalPoint>,
+    fit: FitMode,
+}
+
+fn framing_for(img_path: &Path, settings: &FrameSettings, library: &LibraryIndex) -> Framing {
+    let entry = img_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .and_then(|n| library.pictures.get(n));
+    Framing {
+        rotation: entry.map(|e| e.rotation).unwrap_or_default(),
+        crop: entry.and_then(|e| e.crop),
+        focal_point: entry.and_then(|e| e.focal_point),
+        fit: settings.fit_mode,
+    }
+}
+
+/// Part of a `w`×`h` texture to show in a `win_w`×`win_h` window when filling
+/// it: as large as the aspect ratio allows, centered on `focus` as far as the
+/// edges permit.
+fn fill_source(w: u32, h: u32, win_w: u32, win_h: u32, focus: FocalPoint) -> Rect {
+    let scale = (win_w as f64 / w as f64).max(win_h as f64 / h as f64);
+    let src_w = ((win_w as f64 / scale) as u32).clamp(1, w);
+    let src_h = ((win_h as f64 / scale) as u32).clamp(1, h);
+    let x = (focus.x * w as f64 - src_w as f64 / 2.0).clamp(0.0, (w - src_w) as f64);
+    let y = (focus.y * h as f64 - src_h as f64 / 2.0).clamp(0.0, (h - src_h) as f64);
+    Rect::new(x as i32, y as i32, src_w, src_h)
+}
+
+/// Text drawn over a picture in a translucent strip.
+struct Caption<'a, 'ttf> {
+    font: &'a Font<'ttf, 'static>,
+    text: &'a str,
+    position: CaptionPosition,
+}
+
+/// `None` disables captions, e.g. when SDL_ttf could not be initialised.
+fn load_caption_font(ttf: Option<&Sdl2TtfContext>, size: u16) -> Option<Font<'_, 'static>> {
+    ttf?.load_font(&CONFIG.display_caption_font, size)
+        .inspect_err(|e| {
+            tracing::warn!(
+                "cannot load caption font {}: {e}",
+                CONFIG.display_caption_font
+            )
+        })
+        .ok()
+}
+
+/// The caption to draw over `img_path`, if captions are enabled and it has one.
+fn caption_for<'a, 'ttf>(
+    img_path: &'a Path,
+    settings: &FrameSettings,
+    library: &'a LibraryIndex,
+    font: Option<&'a Font<'ttf, 'static>>,
+) -> Option<Caption<'a, 'ttf>> {
+    if !settings.caption_enabled {
+        return None;
+    }
+    let name = img_path.file_name()?.to_str()?;
+    Some(Caption {
+        font: font?,
+        text: library.caption(name)?,
+        position: settings.caption_position,
+    })
+}
+
+/// Draw a caption strip across the top or bottom of the window.
+fn draw_caption(
+    canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+    tex_creator: &sdl2::render::TextureCreator<sdl2::video::WindowContext>,
+    caption: &Caption,
+) -> Result<()> {
+    let (win_w, win_h) = canvas.output_size().map_err(anyhow::Error::msg)?;
+    let pad = caption.font.height().max(0) as u32 / 2;
+
+    let surface = caption
+        .font
+        .render(caption.text)
+        .blended_wrapped(Color::WHITE, win_w.saturating_sub(2 * pad).max(1))
+        .context("render caption")?;
+    let text = tex_creator
+        .create_texture_from_surface(&surface)
+        .context("create caption texture")?;
+
+    let strip_h = (surface.height() + 2 * pad).min(win_h);
+    let strip_y = match caption.position {
+        CaptionPosition::Top => 0,
+        CaptionPosition::Bottom => (win_h - strip_h) as i32,
+    };
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 150));
+    let filled = canvas.fill_rect(Rect::new(0, strip_y, win_w, strip_h));
+    canvas.set_blend_mode(BlendMode::None);
+    canvas.set_draw_color(Color::BLACK);
+    filled.map_err(anyhow::Error::msg)?;
+
+    let dst = Rect::new(
+        (win_w.saturating_sub(surface.width()) / 2) as i32,
+        strip_y + pad as i32,
+        surface.width(),
+        surface.height(),
+    );
+    canvas.copy(&text, None, dst).map_err(anyhow::Error::msg)?;
+    Ok(())
+}
+
+/// Load an image and blit it full‑screen (keep aspect), cut and scaled as
+/// `framing` says, with an optional caption on top.
 fn show_image(
     canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
     tex_creator: &sdl2::render::TextureCreator<sdl2::video::WindowContext>,
     img_path: &Path,
+    framing: Framing,
+    caption: Option<Caption>,
 ) -> Result<()> {
     let img_bytes = std::fs::read(img_path)?;
     let exif_orientation = ExifReader::new()
@@ -79,6 +265,7 @@ fn show_image(
         })
         .unwrap_or(1);
 
+    // format is sniffed from the content; animated GIFs decode to their first frame
     let mut dyn_img =
         image::load_from_memory(&img_bytes).with_context(|| format!("loading {img_path:?}"))?;
 
@@ -98,6 +285,20 @@ fn show_image(
         _ => dyn_img,
     };
 
+    // then the manual override, if any
+    dyn_img = framing.rotation.apply(dyn_img);
+
+    let mut focus = framing.focal_point.unwrap_or_default();
+    if let Some(crop) = framing.crop {
+        let (w, h) = dyn_img.dimensions();
+        let x = (crop.x * w as f64) as u32;
+        let y = (crop.y * h as f64) as u32;
+        let crop_w = ((crop.width * w as f64) as u32).clamp(1, w - x.min(w - 1));
+        let crop_h = ((crop.height * h as f64) as u32).clamp(1, h - y.min(h - 1));
+        dyn_img = dyn_img.crop_imm(x, y, crop_w, crop_h);
+        focus = focus.within(&crop);
+    }
+
     // resize if needed
     let (w, h) = dyn_img.dimensions();
     let max_dimension = 2048;
@@ -127,15 +328,29 @@ fn show_image(
 
     // scale to window while preserving aspect-ratio
     let (win_w, win_h) = canvas.output_size().unwrap();
-    let scale = (win_w as f32 / scaled_w as f32).min(win_h as f32 / scaled_h as f32);
-    let dst = Rect::from_center(
-        (win_w as i32 / 2, win_h as i32 / 2),
-        (scaled_w as f32 * scale) as u32,
-        (scaled_h as f32 * scale) as u32,
-    );
+    let (src, dst) = match framing.fit {
+        FitMode::Fit => {
+            let scale = (win_w as f32 / scaled_w as f32).min(win_h as f32 / scaled_h as f32);
+            let dst = Rect::from_center(
+                (win_w as i32 / 2, win_h as i32 / 2),
+                (scaled_w as f32 * scale) as u32,
+                (scaled_h as f32 * scale) as u32,
+            );
+            (None, Some(dst))
+        }
+        FitMode::Fill => (
+            Some(fill_source(scaled_w, scaled_h, win_w, win_h, focus)),
+            None,
+        ),
+    };
 
     canvas.clear();
-    canvas.copy(&tex, None, dst).unwrap();
+    canvas.copy(&tex, src, dst).unwrap();
+    if let Some(caption) = caption
+        && let Err(e) = draw_caption(canvas, tex_creator, &caption)
+    {
+        tracing::warn!("caption error: {e:#}");
+    }
     canvas.present();
     Ok(())
 }
@@ -154,14 +369,15 @@ async fn main() -> Result<()> {
     let mut current_settings = shared_settings.get().await.clone();
     tracing::info!(?current_settings, "initial settings");
 
+    let library_path = PathBuf::from(&CONFIG.backend_library_index_file);
+    let mut library = load_library_index(&library_path);
+
     let data_dir = PathBuf::from(&CONFIG.backend_data_dir);
-    let mut images = scan_images(&data_dir);
+    // schedules are evaluated against this day until the list is rebuilt
+    let mut today = Local::now().date_naive();
+    let mut images = collect_images(&data_dir, &current_settings, &library, today);
     tracing::info!(count = images.len(), "initial image scan");
 
-    if current_settings.shuffle {
-        images.shuffle(&mut rand::rng());
-    }
-
     let mut index: usize = if let Some(pinned) = &current_settings.pinned_image {
         find_pinned_image_index(&images, pinned).unwrap_or(0)
     } else {
@@ -180,6 +396,9 @@ async fn main() -> Result<()> {
         )?;
         w.watch(&data_dir, RecursiveMode::NonRecursive)?;
         w.watch(settings_path.parent().unwrap(), RecursiveMode::NonRecursive)?;
+        if library_path.parent() != settings_path.parent() {
+            w.watch(library_path.parent().unwrap(), RecursiveMode::NonRecursive)?;
+        }
         (w, rx)
     };
 
@@ -205,6 +424,11 @@ async fn main() -> Result<()> {
         .unwrap();
     let tex_creator = canvas.texture_creator();
 
+    let ttf = sdl2::ttf::init()
+        .inspect_err(|e| tracing::warn!("cannot init SDL_ttf, captions are disabled: {e}"))
+        .ok();
+    let mut caption_font = load_caption_font(ttf.as_ref(), current_settings.caption_font_size);
+
     canvas.set_draw_color(Color::BLACK);
     canvas.clear();
     canvas.present();
@@ -235,14 +459,30 @@ async fn main() -> Result<()> {
                             || p == &settings_path
                     });
 
+                let affects_library = ev.paths.iter()
+                    .any(|p| {
+                        let canon = fs::canonicalize(p).ok();
+                        canon.as_ref() == Some(&library_path)
+                            || p == &library_path
+                    });
+
                 let in_data_dir = ev.paths.iter().any(|p| p.starts_with(&data_dir));
 
+                let mut rebuild = false;
                 if is_relevant {
                     if affects_settings {
                         if let Ok(toml) = fs::read_to_string(&settings_path) {
                             if let Ok(new_settings) = toml::from_str::<FrameSettings>(&toml) {
                                 if new_settings != *shared_settings.settings_store.read().await {
                                     *shared_settings.settings_store.write().await = new_settings.clone();
+                                    if new_settings.caption_font_size != current_settings.caption_font_size {
+                                        caption_font = load_caption_font(ttf.as_ref(), new_settings.caption_font_size);
+                                    }
+                                    rebuild = new_settings.active_albums != current_settings.active_albums
+                                        || new_settings.tag_filter != current_settings.tag_filter
+                                        || new_settings.shuffle != current_settings.shuffle
+                                        || new_settings.shuffle_mode != current_settings.shuffle_mode
+                                        || new_settings.favorite_weight != current_settings.favorite_weight;
                                     current_settings = new_settings;
                                     tracing::debug!(?current_settings, "reloaded settings.toml");
                                     next_switch = Instant::now();
@@ -253,27 +493,41 @@ async fn main() -> Result<()> {
                         } else {
                             tracing::warn!("Could not read settings file after event");
                         }
+                    } else if affects_library {
+                        library = load_library_index(&library_path);
+                        tracing::debug!(albums = library.albums.len(), "reloaded library index");
+                        // tags, weights and schedules may all have changed
+                        rebuild = true;
                     } else if in_data_dir {
-                        images = scan_images(&data_dir);
-                        tracing::debug!(count = images.len(), "image folder rescan");
-                        if current_settings.shuffle {
-                            images.shuffle(&mut rand::rng());
-                        };
-                        index = if let Some(pinned) = &current_settings.pinned_image {
-                            find_pinned_image_index(&images, pinned).unwrap_or(0)
-                        } else {
-                            0
-                        };
-                        if images.is_empty() {
-                            canvas.set_draw_color(Color::BLACK);
-                            canvas.clear();
-                            canvas.present();
-                        };
+                        rebuild = true;
                     }
                 }
+
+                if rebuild {
+                    images = collect_images(&data_dir, &current_settings, &library, today);
+                    tracing::debug!(count = images.len(), "image list rebuilt");
+                    index = if let Some(pinned) = &current_settings.pinned_image {
+                        find_pinned_image_index(&images, pinned).unwrap_or(0)
+                    } else {
+                        0
+                    };
+                    if images.is_empty() {
+                        canvas.set_draw_color(Color::BLACK);
+                        canvas.clear();
+                        canvas.present();
+                    };
+                }
             }
 
             _ = tokio::time::sleep_until(next_switch), if current_settings.display_enabled => {
+                let now = Local::now().date_naive();
+                if now != today {
+                    today = now;
+                    images = collect_images(&data_dir, &current_settings, &library, today);
+                    tracing::debug!(count = images.len(), %today, "new day, image list rebuilt");
+                    // start the new list from its first image
+                    index = images.len().saturating_sub(1);
+                }
                 if !images.is_empty() {
                     if let Some(pinned) = &current_settings.pinned_image {
                         if let Some(pinned_index) = find_pinned_image_index(&images, pinned) {
@@ -283,7 +537,9 @@ async fn main() -> Result<()> {
                                 total = images.len(),
                                 "showing pinned image"
                             );
-                            if let Err(e) = show_image(&mut canvas, &tex_creator, &images[index]) {
+                            let framing = framing_for(&images[index], &current_settings, &library);
+                            let caption = caption_for(&images[index], &current_settings, &library, caption_font.as_ref());
+                            if let Err(e) = show_image(&mut canvas, &tex_creator, &images[index], framing, caption) {
                                 tracing::error!("display error: {e:#}");
                             }
                         } else {
@@ -300,7 +556,9 @@ async fn main() -> Result<()> {
                             interval = current_settings.rotate_interval_secs,
                             "showing next image"
                         );
-                        if let Err(e) = show_image(&mut canvas, &tex_creator, &images[index]) {
+                        let framing = framing_for(&images[index], &current_settings, &library);
+                        let caption = caption_for(&images[index], &current_settings, &library, caption_font.as_ref());
+                        if let Err(e) = show_image(&mut canvas, &tex_creator, &images[index], framing, caption) {
                             tracing::error!("display error: {e:#}");
                         }
                     }
@@ -7,7 +7,92 @@ pub struct FrameSettings {
     pub display_enabled: bool,
     pub rotate_interval_secs: u64,
     pub shuffle: bool,
+    /// How the rotation is shuffled when `shuffle` is on.
+    #[serde(default)]
+    pub shuffle_mode: ShuffleMode,
+    /// With weighted shuffling, favorites count this many times their weight.
+    #[serde(default = "default_favorite_weight")]
+    pub favorite_weight: u32,
     pub pinned_image: Option<String>,
+    /// Album ids whose pictures make up the rotation; empty means all pictures.
+    #[serde(default)]
+    pub active_albums: Vec<String>,
+    /// Narrows the rotation down further by tags.
+    #[serde(default)]
+    pub tag_filter: Option<TagFilter>,
+    /// Draw each picture's caption (or title) over it.
+    #[serde(default)]
+    pub caption_enabled: bool,
+    #[serde(default = "default_caption_font_size")]
+    pub caption_font_size: u16,
+    #[serde(default)]
+    pub caption_position: CaptionPosition,
+    #[serde(default)]
+    pub fit_mode: FitMode,
+}
+
+fn default_caption_font_size() -> u16 {
+    28
+}
+
+fn default_favorite_weight() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShuffleMode {
+    /// Every picture once per round, in random order.
+    #[default]
+    Uniform,
+    /// Pictures come up in proportion to their weight.
+    Weighted,
+}
+
+/// How a picture is scaled to the screen.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FitMode {
+    /// All of the picture, with black bars where the aspect ratios differ.
+    #[default]
+    Fit,
+    /// The whole screen, cutting off what doesn't fit around the focal point.
+    Fill,
+}
+
+/// Edge of the screen the caption strip sits on.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionPosition {
+    Top,
+    #[default]
+    Bottom,
+}
+
+/// Tag conditions a picture has to meet; empty lists impose nothing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TagFilter {
+    /// Must carry every one of these.
+    #[serde(default)]
+    pub all: Vec<String>,
+    /// Must carry at least one of these.
+    #[serde(default)]
+    pub any: Vec<String>,
+    /// Must carry none of these.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn is_empty(&self) -> bool {
+        self.all.is_empty() && self.any.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, tags: &[String]) -> bool {
+        self.all.iter().all(|t| tags.contains(t))
+            && (self.any.is_empty() || self.any.iter().any(|t| tags.contains(t)))
+            && !self.exclude.iter().any(|t| tags.contains(t))
+    }
 }
 
 #[derive(Clone)]
@@ -29,7 +114,15 @@ impl SharedSettings {
                 display_enabled: true,
                 rotate_interval_secs: 10,
                 shuffle: false,
+                shuffle_mode: ShuffleMode::Uniform,
+                favorite_weight: default_favorite_weight(),
                 pinned_image: None,
+                active_albums: Vec::new(),
+                tag_filter: None,
+                caption_enabled: false,
+                caption_font_size: default_caption_font_size(),
+                caption_position: CaptionPosition::Bottom,
+                fit_mode: FitMode::Fit,
             };
             let toml_str = toml::to_string_pretty(&default).map_err(io::Error::other)?;
             fs::write(&settings_path, toml_str)?;
@@ -0,0 +1,179 @@
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+/// Part of a picture to show instead of all of it. Coordinates are
+/// fractions of the picture's width and height, measured after applying
+/// its orientation and [`Rotation`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Crop {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Crop {
+    /// Whether the rectangle is non-empty and lies within the picture, give
+    /// or take rounding.
+    pub fn is_valid(&self) -> bool {
+        const EPSILON: f64 = 1e-9;
+        self.x >= 0.0
+            && self.y >= 0.0
+            && self.width > 0.0
+            && self.height > 0.0
+            && self.x + self.width <= 1.0 + EPSILON
+            && self.y + self.height <= 1.0 + EPSILON
+    }
+
+    /// The same part of the picture once it is turned by `to` instead of `from`.
+    pub fn rotated(&self, from: Rotation, to: Rotation) -> Crop {
+        let (x1, y1) = from.remap(to, (self.x, self.y));
+        let (x2, y2) = from.remap(to, (self.x + self.width, self.y + self.height));
+        Crop {
+            x: x1.min(x2),
+            y: y1.min(y2),
+            width: (x1 - x2).abs(),
+            height: (y1 - y2).abs(),
+        }
+    }
+}
+
+/// Spot that must stay visible when the display has to cut off part of a
+/// picture, in fractions of the whole picture like [`Crop`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct FocalPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl FocalPoint {
+    pub fn is_valid(&self) -> bool {
+        (0.0..=1.0).contains(&self.x) && (0.0..=1.0).contains(&self.y)
+    }
+
+    /// The same spot once the picture is turned by `to` instead of `from`.
+    pub fn rotated(&self, from: Rotation, to: Rotation) -> FocalPoint {
+        let (x, y) = from.remap(to, (self.x, self.y));
+        FocalPoint { x, y }
+    }
+
+    /// The same spot in fractions of `crop`, clamped to its edges.
+    pub fn within(&self, crop: &Crop) -> FocalPoint {
+        FocalPoint {
+            x: ((self.x - crop.x) / crop.width).clamp(0.0, 1.0),
+            y: ((self.y - crop.y) / crop.height).clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Default for FocalPoint {
+    fn default() -> Self {
+        FocalPoint { x: 0.5, y: 0.5 }
+    }
+}
+
+/// Correction applied on top of a picture's EXIF orientation: turned
+/// clockwise by `degrees`, then mirrored horizontally if `flip` is set.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Rotation {
+    pub degrees: u16,
+    #[serde(default)]
+    pub flip: bool,
+}
+
+impl Rotation {
+    pub fn is_valid(&self) -> bool {
+        matches!(self.degrees, 0 | 90 | 180 | 270)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.degrees == 0 && !self.flip
+    }
+
+    /// Turn and mirror an image that already has its EXIF orientation applied.
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        let img = match self.degrees {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            _ => img,
+        };
+        if self.flip {
+            img.fliph()
+        } else {
+            img
+        }
+    }
+
+    /// Move a point given in fractions of the picture as turned by `self` to
+    /// where it lies when the picture is turned by `to`.
+    fn remap(&self, to: Rotation, (x, y): (f64, f64)) -> (f64, f64) {
+        let mirror = |flip: bool, (x, y): (f64, f64)| if flip { (1.0 - x, y) } else { (x, y) };
+        let unturned = turn((360 - self.degrees % 360) % 360, mirror(self.flip, (x, y)));
+        mirror(to.flip, turn(to.degrees, unturned))
+    }
+}
+
+/// Where a point ends up when the picture is turned clockwise by `degrees`.
+fn turn(degrees: u16, (x, y): (f64, f64)) -> (f64, f64) {
+    match degrees {
+        90 => (1.0 - y, x),
+        180 => (1.0 - x, 1.0 - y),
+        270 => (y, 1.0 - x),
+        _ => (x, y),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotations() -> impl Iterator<Item = Rotation> {
+        [false, true].into_iter().flat_map(|flip| {
+            [0, 90, 180, 270]
+                .into_iter()
+                .map(move |degrees| Rotation { degrees, flip })
+        })
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn focal_point_follows_a_quarter_turn() {
+        // the top left corner ends up top right when turned clockwise
+        let corner = FocalPoint { x: 0.0, y: 0.0 };
+        let quarter = Rotation {
+            degrees: 90,
+            flip: false,
+        };
+        let turned = corner.rotated(Rotation::default(), quarter);
+        assert!(close(turned.x, 1.0) && close(turned.y, 0.0), "{turned:?}");
+    }
+
+    #[test]
+    fn crop_keeps_its_area_and_round_trips() {
+        let crop = Crop {
+            x: 0.1,
+            y: 0.2,
+            width: 0.3,
+            height: 0.5,
+        };
+        for from in rotations() {
+            for to in rotations() {
+                let turned = crop.rotated(from, to);
+                assert!(turned.is_valid(), "{from:?} -> {to:?}: {turned:?}");
+                assert!(close(turned.width * turned.height, 0.15));
+                let back = turned.rotated(to, from);
+                assert!(
+                    close(back.x, crop.x)
+                        && close(back.y, crop.y)
+                        && close(back.width, crop.width)
+                        && close(back.height, crop.height),
+                    "{from:?} -> {to:?}: {back:?}"
+                );
+            }
+        }
+    }
+}
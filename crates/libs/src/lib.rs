@@ -1,2 +1,5 @@
 pub mod frame_settings;
+pub mod framing;
+pub mod library_index;
+pub mod schedule;
 pub mod util;
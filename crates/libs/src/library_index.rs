@@ -0,0 +1,121 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+    path::Path,
+};
+
+use crate::{
+    framing::{Crop, FocalPoint, Rotation},
+    schedule::{self, ScheduleRule},
+};
+
+/// What the backend knows about the pictures beyond the files themselves.
+/// Written by the backend whenever it changes, read by the display.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LibraryIndex {
+    /// Keyed by album id.
+    #[serde(default)]
+    pub albums: BTreeMap<String, AlbumEntry>,
+    /// Keyed by file name; only pictures with something to say are listed.
+    #[serde(default)]
+    pub pictures: BTreeMap<String, PictureEntry>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PictureEntry {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub favorite: bool,
+    /// Relative frequency in weighted shuffles; 1 when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduleRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crop: Option<Crop>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focal_point: Option<FocalPoint>,
+    #[serde(default, skip_serializing_if = "Rotation::is_identity")]
+    pub rotation: Rotation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AlbumEntry {
+    pub name: String,
+    /// File names (relative to the data dir) of the album's pictures.
+    #[serde(default)]
+    pub pictures: Vec<String>,
+}
+
+impl LibraryIndex {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let toml = fs::read_to_string(path)?;
+        toml::from_str(&toml).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write to disk atomically.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("toml.tmp");
+        let s = toml::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(&tmp, s)?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Tags of the picture stored as `filename`.
+    pub fn tags(&self, filename: &str) -> &[String] {
+        self.pictures
+            .get(filename)
+            .map(|p| p.tags.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Text to show over the picture stored as `filename`: its caption, or
+    /// its title if it has no caption.
+    pub fn caption(&self, filename: &str) -> Option<&str> {
+        let entry = self.pictures.get(filename)?;
+        entry.caption.as_deref().or(entry.title.as_deref())
+    }
+
+    /// How often the picture stored as `filename` should come up in a
+    /// weighted shuffle: its weight, times `favorite_weight` for favorites.
+    pub fn weight(&self, filename: &str, favorite_weight: u32) -> u32 {
+        let Some(entry) = self.pictures.get(filename) else {
+            return 1;
+        };
+        let weight = entry.weight.unwrap_or(1).max(1);
+        if entry.favorite {
+            weight.saturating_mul(favorite_weight.max(1))
+        } else {
+            weight
+        }
+    }
+
+    /// Whether the picture stored as `filename` may be shown on `date`.
+    pub fn is_scheduled(&self, filename: &str, date: NaiveDate) -> bool {
+        self.pictures
+            .get(filename)
+            .is_none_or(|p| schedule::is_scheduled(&p.schedule, date))
+    }
+
+    /// File names of the pictures in any of `album_ids`, or `None` if none of
+    /// them is known (e.g. the index is older than the settings).
+    pub fn album_members(&self, album_ids: &[String]) -> Option<HashSet<&str>> {
+        let mut known = album_ids
+            .iter()
+            .filter_map(|id| self.albums.get(id))
+            .peekable();
+        known.peek()?;
+        Some(
+            known
+                .flat_map(|album| album.pictures.iter().map(String::as_str))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Limits when a picture is shown. A picture with several rules is shown
+/// while any of them matches; one without rules is always shown.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScheduleRule {
+    /// Between two dates, both inclusive; a missing bound is open.
+    Dates {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        from: Option<NaiveDate>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<NaiveDate>,
+    },
+    /// Every year between two days, both inclusive. Wraps around the turn of
+    /// the year when `from` comes after `until`.
+    Yearly { from: MonthDay, until: MonthDay },
+}
+
+impl ScheduleRule {
+    pub fn matches(&self, date: NaiveDate) -> bool {
+        match *self {
+            ScheduleRule::Dates { from, until } => {
+                from.is_none_or(|from| from <= date) && until.is_none_or(|until| date <= until)
+            }
+            ScheduleRule::Yearly { from, until } => {
+                let day = MonthDay::of(date);
+                if from <= until {
+                    from <= day && day <= until
+                } else {
+                    from <= day || day <= until
+                }
+            }
+        }
+    }
+
+    /// What is wrong with a rule that is unbounded or can never match.
+    pub fn problem(&self) -> Option<&'static str> {
+        match *self {
+            ScheduleRule::Dates {
+                from: None,
+                until: None,
+            } => Some("a dates rule needs from or until"),
+            ScheduleRule::Dates {
+                from: Some(from),
+                until: Some(until),
+            } if from > until => Some("from must not be after until"),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `rules` let a picture be shown on `date`.
+pub fn is_scheduled(rules: &[ScheduleRule], date: NaiveDate) -> bool {
+    rules.is_empty() || rules.iter().any(|r| r.matches(date))
+}
+
+/// A day of the year, written `MM-DD`. `02-29` only occurs in leap years.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MonthDay {
+    month: u32,
+    day: u32,
+}
+
+impl MonthDay {
+    pub fn new(month: u32, day: u32) -> Option<Self> {
+        // 2000 is a leap year, so every day that can occur is accepted
+        NaiveDate::from_ymd_opt(2000, month, day).map(|_| MonthDay { month, day })
+    }
+
+    pub fn of(date: NaiveDate) -> Self {
+        MonthDay {
+            month: date.month(),
+            day: date.day(),
+        }
+    }
+}
+
+impl TryFrom<String> for MonthDay {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid day {s:?}, expected MM-DD");
+        let (month, day) = s.split_once('-').ok_or_else(invalid)?;
+        let month = month.parse().map_err(|_| invalid())?;
+        let day = day.parse().map_err(|_| invalid())?;
+        MonthDay::new(month, day).ok_or_else(invalid)
+    }
+}
+
+impl From<MonthDay> for String {
+    fn from(d: MonthDay) -> Self {
+        d.to_string()
+    }
+}
+
+impl fmt::Display for MonthDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}-{:02}", self.month, self.day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn day(m: u32, d: u32) -> MonthDay {
+        MonthDay::new(m, d).unwrap()
+    }
+
+    #[test]
+    fn empty_schedule_always_shows() {
+        assert!(is_scheduled(&[], date(2024, 2, 29)));
+        assert!(is_scheduled(&[], date(1970, 1, 1)));
+    }
+
+    #[test]
+    fn yearly_rule_wraps_over_new_year() {
+        let holidays = ScheduleRule::Yearly {
+            from: day(12, 20),
+            until: day(1, 6),
+        };
+        for shown in [
+            date(2024, 12, 20),
+            date(2024, 12, 31),
+            date(2025, 1, 1),
+            date(2025, 1, 6),
+        ] {
+            assert!(holidays.matches(shown), "{shown}");
+        }
+        for hidden in [date(2024, 12, 19), date(2025, 1, 7), date(2025, 6, 15)] {
+            assert!(!holidays.matches(hidden), "{hidden}");
+        }
+    }
+
+    #[test]
+    fn bounds_include_the_whole_day() {
+        let until = ScheduleRule::Dates {
+            from: None,
+            until: Some(date(2025, 3, 1)),
+        };
+        assert!(until.matches(date(2025, 3, 1)));
+        assert!(!until.matches(date(2025, 3, 2)));
+
+        let birthday = ScheduleRule::Yearly {
+            from: day(7, 4),
+            until: day(7, 4),
+        };
+        assert!(birthday.matches(date(2030, 7, 4)));
+        assert!(!birthday.matches(date(2030, 7, 3)) && !birthday.matches(date(2030, 7, 5)));
+        // any matching rule is enough
+        assert!(is_scheduled(&[until, birthday], date(2030, 7, 4)));
+        assert!(!is_scheduled(&[until, birthday], date(2030, 7, 5)));
+    }
+}
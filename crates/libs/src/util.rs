@@ -18,3 +18,51 @@ pub fn get_config_dir() -> std::path::PathBuf {
     std::fs::create_dir_all(&config_dir).expect("Failed to create config directory");
     config_dir
 }
+
+/// Extensions of the picture formats the frame can store and display.
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "tif", "tiff", "bmp"];
+
+/// Whether `path` has one of the [`IMAGE_EXTENSIONS`] (case-insensitive).
+pub fn is_image_path(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn recognises_image_extensions() {
+        for name in [
+            "a.jpg", "a.jpeg", "a.png", "a.webp", "a.gif", "a.tif", "a.tiff", "a.bmp",
+        ] {
+            assert!(is_image_path(Path::new(name)), "{name}");
+        }
+    }
+
+    #[test]
+    fn ignores_case() {
+        assert!(is_image_path(Path::new("IMG_0001.JPG")));
+        assert!(is_image_path(Path::new("scan.TiFf")));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        for name in [
+            "a.heic",
+            "a.mp4",
+            "a.txt",
+            "jpg",
+            "a",
+            ".png.part",
+            "a.jpg.part",
+        ] {
+            assert!(!is_image_path(Path::new(name)), "{name}");
+        }
+    }
+}